@@ -19,6 +19,10 @@ pub struct CallLoadProof<'info> {
     pub system_program: Program<'info, System>,
 
     pub polymer_prover: Program<'info, PolymerProver>,
+
+    /// CHECK: checked by the callee, which rejects loading while paused. Optional on the callee so
+    /// callers of older versions keep working
+    pub internal: Option<UncheckedAccount<'info>>,
 }
 
 #[derive(Accounts)]
@@ -38,8 +42,17 @@ pub struct CallValidateEvent<'info> {
     pub system_program: Program<'info, System>,
     pub polymer_prover: Program<'info, PolymerProver>,
 
-    /// CHECK: PDA will be created in the callee if needed
+    /// CHECK: PDA will be created in the callee if needed. If it's passed writable, the callee
+    /// records the latest peptide height in it
     pub internal: UncheckedAccount<'info>,
+
+    /// CHECK: state roots revoked on the callee, which checks the seeds. Optional on the callee
+    /// while nothing is revoked
+    pub revocations: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: config of the proof's source chain, checked by the callee. Optional on the callee
+    /// until source chains are configured
+    pub chain_config: Option<UncheckedAccount<'info>>,
 }
 
 /// this simple program is meant to be used only for testing the CPI capabilities of our
@@ -63,7 +76,7 @@ pub mod cpi_client {
     pub fn call_load_proof(ctx: Context<CallLoadProof>) -> Result<()> {
         msg!("calling load_proof from cpi_client");
 
-        let proof = Vec::from_hex(&PROOF_HEX.trim_start_matches("0x")).expect("could not load proof?");
+        let proof = Vec::from_hex(PROOF_HEX.trim_start_matches("0x")).expect("could not load proof?");
 
         polymer_prover::cpi::load_proof(
             CpiContext::new(
@@ -71,6 +84,8 @@ pub mod cpi_client {
                 PolymerLoadProof {
                     cache_account: ctx.accounts.cache_account.to_account_info(),
                     authority: ctx.accounts.authority.to_account_info(),
+                    system_program: Some(ctx.accounts.system_program.to_account_info()),
+                    internal: ctx.accounts.internal.as_ref().map(|a| a.to_account_info()),
                 },
             ),
             proof,
@@ -88,20 +103,24 @@ pub mod cpi_client {
                 cache_account: ctx.accounts.cache_account.to_account_info(),
                 result_account: ctx.accounts.result_account.to_account_info(),
                 internal: ctx.accounts.internal.to_account_info(),
+                revocations: ctx.accounts.revocations.as_ref().map(|a| a.to_account_info()),
+                chain_config: ctx.accounts.chain_config.as_ref().map(|a| a.to_account_info()),
+                system_program: Some(ctx.accounts.system_program.to_account_info()),
             },
         ))?;
 
-        ctx.accounts.result_account.reload()?;
-        if ctx.accounts.result_account.is_valid {
+        // the result comes back as return data, so there's no need to reload the result account
+        let result = polymer_prover::get_validation_return()?;
+        if result.is_valid {
             // don't bother emitting all the parsed event. Just print something here so we can
             // assert on the test
             msg!(
                 "proof validated: chain_id: {}, emitting_contract: 0x{}",
-                ctx.accounts.result_account.chain_id,
-                hex::encode(&ctx.accounts.result_account.emitting_contract)
+                result.chain_id,
+                hex::encode(result.emitting_contract)
             )
         } else {
-            msg!("prover returned error: {}", ctx.accounts.result_account.error_message);
+            msg!("prover returned error {}", result.error_code);
         }
 
         Ok(())
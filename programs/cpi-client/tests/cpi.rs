@@ -0,0 +1,67 @@
+//! Calls the prover through the cpi client, with mollusk against the compiled programs. Ignored by
+//! default since they need to be built with `make build`, `make test` runs it
+
+#[path = "../../polymer-prover/tests/common/mod.rs"]
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::*;
+use polymer_prover::ValidationResultAccount;
+use solana_sdk::{instruction::Instruction, system_program};
+
+#[test]
+#[ignore = "needs the programs built with `make build`"]
+fn test_cpi_calls() {
+    let mut harness = Harness::new();
+    harness.add_program(cpi_client::ID, "cpi_client");
+
+    let authority = harness.new_wallet();
+    harness.process_ok(&initialize(authority));
+
+    let user = harness.new_wallet();
+    harness.process_ok(&create_accounts(user));
+
+    let load = Instruction {
+        program_id: cpi_client::ID,
+        accounts: cpi_client::accounts::CallLoadProof {
+            cache_account: cache_pda(&user),
+            authority: user,
+            system_program: system_program::ID,
+            polymer_prover: polymer_prover::ID,
+            internal: Some(internal_pda()),
+        }
+        .to_account_metas(None),
+        data: cpi_client::instruction::CallLoadProof.data(),
+    };
+    let validate = |revocations, chain_config| Instruction {
+        program_id: cpi_client::ID,
+        accounts: cpi_client::accounts::CallValidateEvent {
+            cache_account: cache_pda(&user),
+            result_account: result_pda(&user),
+            authority: user,
+            system_program: system_program::ID,
+            polymer_prover: polymer_prover::ID,
+            internal: internal_pda(),
+            revocations,
+            chain_config,
+        }
+        .to_account_metas(None),
+        data: cpi_client::instruction::CallValidateEvent.data(),
+    };
+    let result = |harness: &Harness| {
+        let account = harness.account(&result_pda(&user));
+        ValidationResultAccount::try_deserialize(&mut account.data.as_slice()).unwrap()
+    };
+
+    // callers that don't pass the accounts added with revocations and chain configs keep working
+    // until they are needed
+    harness.process_ok(&load);
+    harness.process_ok(&validate(None, None));
+    assert!(result(&harness).is_valid);
+
+    harness.process_ok(&set_chain_config(authority, OP_CHAIN_ID, true));
+    harness.process_ok(&load);
+    harness.process_ok(&validate(Some(revocations_pda()), Some(chain_config_pda(OP_CHAIN_ID))));
+    assert!(result(&harness).is_valid);
+    assert_eq!(OP_CHAIN_ID, result(&harness).chain_id);
+}
@@ -0,0 +1,60 @@
+//! Runs mars with mollusk against the compiled program. Ignored by default since it needs to be
+//! built with `make build`, `make test` runs it
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use mars::Data;
+use mollusk_svm::{
+    program::{create_program_account_loader_v3, keyed_account_for_system_program, loader_keys},
+    Mollusk,
+};
+use solana_sdk::{
+    account::Account, instruction::Instruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, system_program,
+};
+use std::path::PathBuf;
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_set_data() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy/mars.so");
+    let elf = std::fs::read(&path).unwrap_or_else(|_| panic!("{} not found, run `make build` first", path.display()));
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_with_elf_and_loader(&mars::ID, &elf, &loader_keys::LOADER_V3);
+
+    let user = Pubkey::new_unique();
+    let data = Pubkey::find_program_address(&[user.as_ref()], &mars::ID).0;
+    let accounts = vec![
+        (data, Account::default()),
+        (user, Account::new(LAMPORTS_PER_SOL, 0, &system_program::ID)),
+        keyed_account_for_system_program(),
+        (mars::ID, create_program_account_loader_v3(&mars::ID)),
+    ];
+
+    let initialize = Instruction {
+        program_id: mars::ID,
+        accounts: mars::accounts::Initialize {
+            data,
+            user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: mars::instruction::Initialize.data(),
+    };
+    let set_data = Instruction {
+        program_id: mars::ID,
+        accounts: mars::accounts::SetData { data }.to_account_metas(None),
+        data: mars::instruction::SetData {
+            data: Data {
+                data: "foo bar zoo".to_string(),
+            },
+        }
+        .data(),
+    };
+
+    let result = mollusk.process_instruction_chain(&[initialize, set_data], &accounts);
+    assert!(!result.program_result.is_err(), "{:?}", result.program_result);
+
+    let (_, account) = result.resulting_accounts.iter().find(|(key, _)| *key == data).unwrap();
+    let account = Data::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!("foo bar zoo", account.data);
+}
@@ -1,2 +1,7 @@
 pub mod parse_event;
+pub mod proof;
+pub mod proof_cache;
+pub mod return_data;
+pub mod revocation;
+pub mod secp256k1_precompile;
 pub mod validate_event;
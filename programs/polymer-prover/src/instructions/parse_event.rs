@@ -28,7 +28,7 @@ impl EthAddress {
     }
 
     pub fn to_hex(&self) -> String {
-        "0x".to_owned() + &hex::encode(&self.0)
+        "0x".to_owned() + &hex::encode(self.0)
     }
 }
 
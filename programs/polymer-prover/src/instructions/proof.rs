@@ -0,0 +1,371 @@
+use anchor_lang::prelude::borsh::{self, BorshDeserialize, BorshSerialize};
+use std::fmt;
+
+/// Versioned proofs start with these bytes followed by the version byte. Proofs without them are
+/// legacy v2 proofs, which were generated before the header existed
+pub const PROOF_MAGIC: [u8; 4] = *b"PLYP";
+
+pub const PROOF_V2: u8 = 2;
+
+/// Same layout as v2 but without the leading state root and attestation. The state root is
+/// submitted beforehand with `submit_state_root` and looked up by the peptide height in the proof
+pub const PROOF_CHECKPOINT: u8 = 3;
+
+/// size of each (signature, recovery id) pair that attests the peptide state root
+pub const ATTESTATION_SIZE: usize = 65;
+
+const HEADER_SIZE: usize = PROOF_MAGIC.len() + 1;
+
+// v2 layout, relative to the end of the header (if any)
+const APP_HASH: std::ops::Range<usize> = 0..32;
+const ATTESTATION: std::ops::Range<usize> = 32..97;
+/// checkpoint proofs don't have these first bytes
+const SIGNED_ROOT_SIZE: usize = 97;
+const CHAIN_ID: std::ops::Range<usize> = 97..101;
+const PEPTIDE_HEIGHT: std::ops::Range<usize> = 101..109;
+const BLOCK_NUMBER: std::ops::Range<usize> = 109..117;
+const TX_INDEX: std::ops::Range<usize> = 117..121;
+const LOG_INDEX: std::ops::Range<usize> = 121..125;
+const NUM_TOPICS: usize = 125;
+const EVENT_END: std::ops::Range<usize> = 126..128;
+const EVENT_START: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    UnsupportedVersion(u8),
+
+    /// got, needed
+    TooShort(usize, usize),
+
+    InvalidEvent(String),
+
+    InvalidMembershipProof(String),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::UnsupportedVersion(version) => write!(f, "unsupported proof version: {}", version),
+            ProofError::TooShort(got, needed) => {
+                write!(f, "got {} bytes, at least {} are needed", got, needed)
+            }
+            ProofError::InvalidEvent(err_msg) => write!(f, "invalid event: {}", err_msg),
+            ProofError::InvalidMembershipProof(err_msg) => write!(f, "invalid membership proof: {}", err_msg),
+        }
+    }
+}
+
+/// Uniquely identifies an event on its source chain
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct EventLocation {
+    pub chain_id: u32,
+    pub block_number: u64,
+    pub tx_index: u32,
+    pub log_index: u32,
+}
+
+impl EventLocation {
+    /// Key under which peptide stores the event
+    pub fn key(&self, client_type: &str) -> String {
+        format!(
+            "chain/{}/storedLogs/{}/{}/{}/{}",
+            self.chain_id, client_type, self.block_number, self.tx_index, self.log_index,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attestation {
+    pub signature: [u8; 64],
+    pub recovery_id: u8,
+}
+
+impl Attestation {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Attestation {
+            signature: <[u8; 64]>::try_from(&bytes[0..64]).unwrap(),
+            recovery_id: bytes[64],
+        }
+    }
+}
+
+/// One of the inner nodes on the path from the leaf to the state root. Its hash is
+/// sha256(prefix || child hash || suffix)
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerStep<'a> {
+    pub prefix: &'a [u8],
+    pub suffix: &'a [u8],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MembershipProof<'a> {
+    pub leaf_prefix: &'a [u8],
+    pub path: Vec<InnerStep<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proof<'a> {
+    pub version: u8,
+
+    /// zero for checkpoint proofs, whose state root is stored on chain
+    pub app_hash: [u8; 32],
+
+    pub peptide_height: u64,
+    pub location: EventLocation,
+    pub num_topics: usize,
+
+    /// emitting contract || topics || unindexed data
+    pub raw_event: &'a [u8],
+
+    pub membership_proof: MembershipProof<'a>,
+
+    /// the first one comes from the proof header. Any extra ones are appended after the
+    /// membership proof. Checkpoint proofs only have the extra ones
+    pub attestations: Vec<Attestation>,
+}
+
+impl<'a> Proof<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Proof<'a>, ProofError> {
+        if !bytes.starts_with(&PROOF_MAGIC) {
+            return Self::parse_v2(PROOF_V2, bytes);
+        }
+
+        if bytes.len() < HEADER_SIZE {
+            return Err(ProofError::TooShort(bytes.len(), HEADER_SIZE));
+        }
+
+        match bytes[PROOF_MAGIC.len()] {
+            PROOF_V2 => Self::parse_v2(PROOF_V2, &bytes[HEADER_SIZE..]),
+            PROOF_CHECKPOINT => Self::parse_layout(PROOF_CHECKPOINT, &bytes[HEADER_SIZE..], SIGNED_ROOT_SIZE),
+            version => Err(ProofError::UnsupportedVersion(version)),
+        }
+    }
+
+    fn parse_v2(version: u8, proof: &'a [u8]) -> Result<Proof<'a>, ProofError> {
+        Self::parse_layout(version, proof, 0)
+    }
+
+    /// Parses the v2 layout with its first `skipped` bytes missing. Offsets in the proof, like
+    /// the event end, are relative to the start of the proof as given
+    fn parse_layout(version: u8, proof: &'a [u8], skipped: usize) -> Result<Proof<'a>, ProofError> {
+        let field = |range: std::ops::Range<usize>| &proof[range.start - skipped..range.end - skipped];
+
+        // first, check there's enough data to read the event_end index
+        let proof_len = proof.len();
+        let event_start = EVENT_START - skipped;
+        if proof_len < event_start {
+            return Err(ProofError::TooShort(proof_len, event_start));
+        }
+
+        let event_end: usize = u16::from_be_bytes(<[u8; 2]>::try_from(field(EVENT_END)).unwrap()).into();
+
+        // now, make sure we have enough data to read until the event ends. After it, we have the
+        // membership proof
+        if proof_len < event_end {
+            return Err(ProofError::TooShort(proof_len, event_end));
+        }
+
+        let raw_event = proof.get(event_start..event_end).unwrap_or_default();
+        let num_topics: usize = proof[NUM_TOPICS - skipped].into();
+        let event_min_len = 20 + 32 * num_topics;
+        if raw_event.len() < event_min_len {
+            return Err(ProofError::InvalidEvent(format!(
+                "got {} bytes for {} topics",
+                raw_event.len(),
+                num_topics
+            )));
+        }
+
+        let (membership_proof, membership_len) = parse_membership_proof(&proof[event_end..])?;
+
+        // whatever is left after the membership proof are the extra attestations
+        let extra_attestations = &proof[event_end + membership_len..];
+        let leftover = extra_attestations.len() % ATTESTATION_SIZE;
+        if leftover != 0 {
+            return Err(ProofError::TooShort(proof_len, proof_len + ATTESTATION_SIZE - leftover));
+        }
+
+        // checkpoint proofs carry neither the state root nor its first attestation
+        let (app_hash, first_attestation) = match skipped {
+            0 => (
+                <[u8; 32]>::try_from(&proof[APP_HASH]).unwrap(),
+                Some(&proof[ATTESTATION]),
+            ),
+            _ => ([0; 32], None),
+        };
+        let attestations = first_attestation
+            .into_iter()
+            .chain(extra_attestations.chunks_exact(ATTESTATION_SIZE))
+            .map(Attestation::from_bytes)
+            .collect();
+
+        Ok(Proof {
+            version,
+            app_hash,
+            peptide_height: u64::from_be_bytes(<[u8; 8]>::try_from(field(PEPTIDE_HEIGHT)).unwrap()),
+            location: EventLocation {
+                chain_id: u32::from_be_bytes(<[u8; 4]>::try_from(field(CHAIN_ID)).unwrap()),
+                block_number: u64::from_be_bytes(<[u8; 8]>::try_from(field(BLOCK_NUMBER)).unwrap()),
+                tx_index: u32::from_be_bytes(<[u8; 4]>::try_from(field(TX_INDEX)).unwrap()),
+                log_index: u32::from_be_bytes(<[u8; 4]>::try_from(field(LOG_INDEX)).unwrap()),
+            },
+            num_topics,
+            raw_event,
+            membership_proof,
+            attestations,
+        })
+    }
+}
+
+/// Parses the membership proof and returns how many bytes of it were consumed
+fn parse_membership_proof(proof: &[u8]) -> Result<(MembershipProof<'_>, usize), ProofError> {
+    let proof_len = proof.len();
+    // first make sure we have enough data to read the start of the first path
+    if proof_len < 2 {
+        return Err(ProofError::InvalidMembershipProof(
+            "can't read start of first path".to_string(),
+        ));
+    }
+
+    let number_of_paths: usize = proof[0].into();
+    let path_zero_start: usize = proof[1].into();
+
+    if path_zero_start < 2 || path_zero_start >= proof_len {
+        return Err(ProofError::InvalidMembershipProof("can't read first path".to_string()));
+    }
+
+    let mut path = Vec::with_capacity(number_of_paths);
+    let mut offset: usize = path_zero_start;
+
+    for _ in 0..number_of_paths {
+        if offset + 1 >= proof_len {
+            return Err(ProofError::InvalidMembershipProof("can't read offsets".to_string()));
+        }
+
+        let suffix_start: usize = proof[offset].into();
+        let suffix_end: usize = proof[offset + 1].into();
+
+        if suffix_start < 2 || suffix_end < suffix_start || offset + suffix_end > proof_len {
+            return Err(ProofError::InvalidMembershipProof("can't read path".to_string()));
+        }
+
+        path.push(InnerStep {
+            prefix: &proof[offset + 2..offset + suffix_start],
+            suffix: &proof[offset + suffix_start..offset + suffix_end],
+        });
+        offset += suffix_end;
+    }
+
+    Ok((
+        MembershipProof {
+            leaf_prefix: &proof[2..path_zero_start],
+            path,
+        },
+        offset,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_proof_file(file_path: &str) -> Vec<u8> {
+        let contents = std::fs::read_to_string(file_path).expect("could not read hex file");
+        hex::decode(&contents.trim().as_bytes()[2..]).expect("could not decode hex file")
+    }
+
+    #[test]
+    fn test_parse_legacy_v2() {
+        let bytes = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+        let proof = Proof::parse(&bytes).unwrap();
+
+        assert_eq!(PROOF_V2, proof.version);
+        assert_eq!(bytes[0..32], proof.app_hash);
+        assert_eq!(3_130_134, proof.peptide_height);
+        assert_eq!(
+            EventLocation {
+                chain_id: 11_155_420,
+                block_number: 23_562_439,
+                tx_index: 1,
+                log_index: 0,
+            },
+            proof.location
+        );
+        assert_eq!(4, proof.num_topics);
+        assert_eq!(&bytes[128..308], proof.raw_event);
+        assert_eq!(18, proof.membership_proof.path.len());
+        assert_eq!(1, proof.attestations.len());
+        assert_eq!(28, proof.attestations[0].recovery_id);
+    }
+
+    #[test]
+    fn test_parse_versioned_v2() {
+        let bytes = read_proof_file("src/instructions/test-data/arb-proof-v2.hex");
+        let mut versioned = PROOF_MAGIC.to_vec();
+        versioned.push(PROOF_V2);
+        versioned.extend(&bytes);
+
+        assert_eq!(Proof::parse(&bytes), Proof::parse(&versioned));
+    }
+
+    #[test]
+    fn test_parse_checkpoint() {
+        let bytes = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+
+        // drop the signed state root and move the event end back accordingly
+        let mut checkpoint = PROOF_MAGIC.to_vec();
+        checkpoint.push(PROOF_CHECKPOINT);
+        checkpoint.extend(&bytes[SIGNED_ROOT_SIZE..]);
+        let event_end = HEADER_SIZE + EVENT_END.start - SIGNED_ROOT_SIZE;
+        checkpoint[event_end..event_end + 2].copy_from_slice(&(308 - SIGNED_ROOT_SIZE as u16).to_be_bytes());
+
+        let v2 = Proof::parse(&bytes).unwrap();
+        let proof = Proof::parse(&checkpoint).unwrap();
+        assert_eq!(
+            Proof {
+                version: PROOF_CHECKPOINT,
+                app_hash: [0; 32],
+                attestations: vec![],
+                ..v2
+            },
+            proof
+        );
+
+        let short = &checkpoint[..HEADER_SIZE + 30];
+        assert_eq!(Err(ProofError::TooShort(30, 31)), Proof::parse(short));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let bytes = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+        let mut versioned = PROOF_MAGIC.to_vec();
+        versioned.push(4);
+        versioned.extend(&bytes);
+
+        assert_eq!(Err(ProofError::UnsupportedVersion(4)), Proof::parse(&versioned));
+        assert_eq!(Err(ProofError::TooShort(4, 5)), Proof::parse(&PROOF_MAGIC));
+    }
+
+    #[test]
+    fn test_event_shorter_than_topics() {
+        let mut bytes = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+        // claim the event ends right after the emitting contract
+        bytes[126..128].copy_from_slice(&148u16.to_be_bytes());
+
+        assert!(matches!(Proof::parse(&bytes), Err(ProofError::InvalidEvent(_))));
+    }
+
+    #[test]
+    fn test_corrupted_proofs_dont_cause_panics() {
+        let bytes = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+
+        // mess with every byte outside the event, one at a time
+        for i in (0..128).chain(308..bytes.len()) {
+            for value in [0u8, 1, 2, 0x7f, 0xff] {
+                let mut corrupted = bytes.clone();
+                corrupted[i] = value;
+                let _ = Proof::parse(&corrupted);
+            }
+        }
+    }
+}
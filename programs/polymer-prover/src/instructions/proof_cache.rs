@@ -0,0 +1,609 @@
+use crate::{
+    ErrorCode, ProofCacheAccount, DEFAULT_PROOF_CAPACITY, DISCRIMINATOR_SIZE, MAX_PROOF_LEN, RECEIVED_BITMAP_LEN,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+
+impl ProofCacheAccount {
+    /// account size needed for proofs of up to `capacity` bytes, loaded either way
+    pub fn space_for(capacity: usize) -> usize {
+        DISCRIMINATOR_SIZE + Self::INIT_SPACE - DEFAULT_PROOF_CAPACITY - RECEIVED_BITMAP_LEN
+            + capacity
+            + capacity.div_ceil(8)
+    }
+
+    /// Empties the cache on the raw account data, so the account doesn't have to be
+    /// deserialized. An empty cache is all zeros after the discriminator
+    pub fn clear_account_data(data: &mut [u8]) -> Result<()> {
+        require!(
+            data.starts_with(Self::DISCRIMINATOR),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        let empty = data
+            .get_mut(DISCRIMINATOR_SIZE..DISCRIMINATOR_SIZE + EMPTY_CACHE_LEN)
+            .ok_or(anchor_lang::error::ErrorCode::AccountDidNotDeserialize)?;
+        empty.fill(0);
+        Ok(())
+    }
+}
+
+/// serialized size of an empty ProofCacheAccount, without the discriminator
+const EMPTY_CACHE_LEN: usize = 4 + 4 + 4 + 4 + 1;
+
+/// offset of the proof in the account data, after the discriminator and the proof length
+const PROOF_START: usize = DISCRIMINATOR_SIZE + 4;
+
+/// The fields of a ProofCacheAccount, read from the raw account data. Chunks are written in place
+/// through it, so loading a chunk doesn't deserialize and serialize the whole proof every time.
+/// The proof and the received bitmap are left in the account data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheLayout {
+    proof_len: usize,
+    total_len: u32,
+    received_len: u32,
+    expected_hash: Option<[u8; 32]>,
+}
+
+impl CacheLayout {
+    /// Reads the layout of the account data, serialized as ProofCacheAccount is. Older versions of
+    /// the program only stored the proof, followed by whatever was left over from previous proofs,
+    /// so if what follows the proof doesn't add up the cache is read as one of those
+    pub fn read(data: &[u8]) -> Result<Self> {
+        require!(
+            data.starts_with(ProofCacheAccount::DISCRIMINATOR),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        let mut reader = Reader(&data[DISCRIMINATOR_SIZE..]);
+        let proof_len = reader.u32()? as usize;
+        reader.bytes(proof_len)?;
+
+        Ok(Self::read_trailer(proof_len, reader).unwrap_or(CacheLayout {
+            proof_len,
+            total_len: 0,
+            received_len: 0,
+            expected_hash: None,
+        }))
+    }
+
+    fn read_trailer(proof_len: usize, mut reader: Reader) -> Result<Self> {
+        let total_len = reader.u32()?;
+        let bitmap_len = reader.u32()? as usize;
+        require!(
+            (total_len == 0 || total_len as usize == proof_len) && bitmap_len == (total_len as usize).div_ceil(8),
+            anchor_lang::error::ErrorCode::AccountDidNotDeserialize
+        );
+        reader.bytes(bitmap_len)?;
+
+        let received_len = reader.u32()?;
+        require!(
+            received_len <= total_len,
+            anchor_lang::error::ErrorCode::AccountDidNotDeserialize
+        );
+        let expected_hash = match reader.bytes(1)? {
+            [0] => None,
+            [1] => Some(reader.bytes(32)?.try_into().unwrap()),
+            _ => return err!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize),
+        };
+
+        Ok(CacheLayout {
+            proof_len,
+            total_len,
+            received_len,
+            expected_hash,
+        })
+    }
+
+    /// length of the received bitmap, only used by proofs loaded with `load_at`
+    fn bitmap_len(&self) -> usize {
+        (self.total_len as usize).div_ceil(8)
+    }
+
+    fn bitmap_start(&self) -> usize {
+        PROOF_START + self.proof_len + 4 + 4
+    }
+
+    /// account size needed to store the current contents
+    pub fn space(&self) -> usize {
+        self.bitmap_start() + self.bitmap_len() + 4 + 1 + self.expected_hash.map_or(0, |_| 32)
+    }
+
+    /// Account size needed to append a chunk of `chunk_len` bytes. It fits proofs of the new
+    /// length loaded either way
+    pub fn space_to_append(&self, chunk_len: usize) -> Result<usize> {
+        require!(self.total_len == 0, ErrorCode::MixedProofLoading);
+        let proof_len = self.proof_len + chunk_len;
+        require!(proof_len <= MAX_PROOF_LEN, ErrorCode::InvalidProofLength);
+        Ok(ProofCacheAccount::space_for(proof_len))
+    }
+
+    /// Appends the chunk to the proof, as load_proof always did. It can't be mixed with `load_at`
+    /// on the same proof. The data must have room for `space_to_append`
+    pub fn append(self, data: &mut [u8], chunk: &[u8]) -> Result<()> {
+        let space = self.space_to_append(chunk.len())?;
+        require!(
+            data.len() >= space,
+            anchor_lang::error::ErrorCode::AccountDidNotSerialize
+        );
+
+        let start = PROOF_START + self.proof_len;
+        data[start..start + chunk.len()].copy_from_slice(chunk);
+
+        CacheLayout {
+            proof_len: self.proof_len + chunk.len(),
+            ..self
+        }
+        .write(data);
+        Ok(())
+    }
+
+    /// Account size needed to load the chunks of a proof of `total_len` bytes
+    pub fn space_to_load(&self, total_len: u32) -> Result<usize> {
+        require!(
+            total_len > 0 && total_len as usize <= MAX_PROOF_LEN,
+            ErrorCode::InvalidProofLength
+        );
+        if self.total_len == 0 {
+            require!(self.proof_len == 0, ErrorCode::MixedProofLoading);
+        } else {
+            require!(self.total_len == total_len, ErrorCode::ProofLengthMismatch);
+        }
+        Ok(ProofCacheAccount::space_for(total_len as usize))
+    }
+
+    /// Writes the chunk at the given offset of a proof of `total_len` bytes. Chunks can be loaded
+    /// in any order and any number of times, so retrying a chunk is always safe. The data must
+    /// have room for `space_to_load`
+    pub fn load_at(self, data: &mut [u8], offset: u32, total_len: u32, chunk: &[u8]) -> Result<()> {
+        let space = self.space_to_load(total_len)?;
+        require!(
+            data.len() >= space,
+            anchor_lang::error::ErrorCode::AccountDidNotSerialize
+        );
+
+        // the first chunk, whichever it is, sets the proof up
+        let mut layout = self;
+        if layout.total_len == 0 {
+            layout = CacheLayout {
+                proof_len: total_len as usize,
+                total_len,
+                received_len: 0,
+                ..layout
+            };
+            data[PROOF_START..layout.bitmap_start() + layout.bitmap_len()].fill(0);
+        }
+
+        let start = offset as usize;
+        let end = start
+            .checked_add(chunk.len())
+            .filter(|end| *end <= layout.proof_len)
+            .ok_or(ErrorCode::ChunkOutOfBounds)?;
+
+        data[PROOF_START + start..PROOF_START + end].copy_from_slice(chunk);
+
+        // keep track of which bytes were received so far, so retried chunks are not counted twice
+        let bitmap_start = layout.bitmap_start();
+        let received = &mut data[bitmap_start..bitmap_start + layout.bitmap_len()];
+        for i in start..end {
+            let (byte, bit) = (i / 8, 1u8 << (i % 8));
+            if received[byte] & bit == 0 {
+                received[byte] |= bit;
+                layout.received_len += 1;
+            }
+        }
+
+        layout.write(data);
+        Ok(())
+    }
+
+    /// account size needed once the expected hash is set
+    pub fn space_with_expected_hash(&self) -> usize {
+        CacheLayout {
+            expected_hash: Some([0; 32]),
+            ..*self
+        }
+        .space()
+    }
+
+    /// Sets the hash the proof must match. It can't be changed once chunks have been loaded,
+    /// otherwise whoever loaded them could just replace it. The data must have room for
+    /// `space_with_expected_hash`
+    pub fn set_expected_hash(self, data: &mut [u8], expected_hash: [u8; 32]) -> Result<()> {
+        require!(
+            self.proof_len == 0 && self.total_len == 0,
+            ErrorCode::ProofAlreadyLoading
+        );
+        require!(
+            data.len() >= self.space_with_expected_hash(),
+            anchor_lang::error::ErrorCode::AccountDidNotSerialize
+        );
+        CacheLayout {
+            expected_hash: Some(expected_hash),
+            ..self
+        }
+        .write(data);
+        Ok(())
+    }
+
+    /// writes everything but the proof and the received bitmap, which are written in place
+    fn write(&self, data: &mut [u8]) {
+        let mut writer = Writer(&mut data[DISCRIMINATOR_SIZE..]);
+        writer.u32(self.proof_len as u32);
+        writer.skip(self.proof_len);
+        writer.u32(self.total_len);
+        writer.u32(self.bitmap_len() as u32);
+        writer.skip(self.bitmap_len());
+        writer.u32(self.received_len);
+        match self.expected_hash {
+            None => writer.bytes(&[0]),
+            Some(hash) => {
+                writer.bytes(&[1]);
+                writer.bytes(&hash);
+            }
+        }
+    }
+}
+
+/// Proof borrowed straight from the data of a ProofCacheAccount. Validating a proof only needs to
+/// read it, so there's no point in deserializing (copying) the whole account first
+pub struct CachedProof<'a> {
+    pub proof: &'a [u8],
+    layout: CacheLayout,
+}
+
+impl<'a> CachedProof<'a> {
+    /// Reads the proof from the account data, laid out as ProofCacheAccount is serialized
+    pub fn from_account_data(data: &'a [u8]) -> Result<Self> {
+        let layout = CacheLayout::read(data)?;
+        Ok(CachedProof {
+            proof: &data[PROOF_START..PROOF_START + layout.proof_len],
+            layout,
+        })
+    }
+
+    /// Whether the proof is ready to be validated. Appended proofs always are, while proofs
+    /// loaded with `load_at` need all their bytes
+    pub fn is_complete(&self) -> bool {
+        self.layout.received_len == self.layout.total_len
+    }
+
+    /// Fails unless the proof is complete and matches the expected hash, if any. This is checked
+    /// before the proof is validated, so no time is wasted recovering signatures
+    pub fn check_ready(&self) -> Result<()> {
+        require!(self.is_complete(), ErrorCode::ProofIncomplete);
+        if let Some(expected_hash) = self.layout.expected_hash {
+            require!(
+                keccak::hash(self.proof).0 == expected_hash,
+                ErrorCode::ProofHashMismatch
+            );
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.0.len() {
+            return err!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+/// Counterpart of Reader. Callers make sure the data is large enough first
+struct Writer<'a>(&'a mut [u8]);
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        let (head, rest) = std::mem::take(&mut self.0).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        self.0 = rest;
+    }
+
+    fn skip(&mut self, len: usize) {
+        self.0 = &mut std::mem::take(&mut self.0)[len..];
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof() -> Vec<u8> {
+        (0..100u8).collect()
+    }
+
+    /// data of a new cache account, as created by create_accounts
+    fn new_account() -> Vec<u8> {
+        let mut data = Vec::new();
+        ProofCacheAccount::default().try_serialize(&mut data).unwrap();
+        data.resize(ProofCacheAccount::space_for(DEFAULT_PROOF_CAPACITY), 0);
+        data
+    }
+
+    /// loads the chunk as load_proof_at does, growing the account if needed
+    fn load_at(data: &mut Vec<u8>, offset: u32, total_len: u32, chunk: &[u8]) -> Result<()> {
+        let layout = CacheLayout::read(data)?;
+        let space = layout.space_to_load(total_len)?;
+        data.resize(space.max(data.len()), 0);
+        layout.load_at(data, offset, total_len, chunk)
+    }
+
+    /// appends the chunk as load_proof does, growing the account if needed
+    fn append(data: &mut Vec<u8>, chunk: &[u8]) -> Result<()> {
+        let layout = CacheLayout::read(data)?;
+        let space = layout.space_to_append(chunk.len())?;
+        data.resize(space.max(data.len()), 0);
+        layout.append(data, chunk)
+    }
+
+    fn set_expected_hash(data: &mut Vec<u8>, expected_hash: [u8; 32]) {
+        let layout = CacheLayout::read(data).unwrap();
+        data.resize(layout.space_with_expected_hash().max(data.len()), 0);
+        layout.set_expected_hash(data, expected_hash).unwrap();
+    }
+
+    fn cached(data: &[u8]) -> CachedProof<'_> {
+        CachedProof::from_account_data(data).unwrap()
+    }
+
+    /// what's written in place must deserialize as the account
+    fn deserialize(data: &[u8]) -> ProofCacheAccount {
+        ProofCacheAccount::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    fn test_load_out_of_order() {
+        let proof = proof();
+        let mut data = new_account();
+
+        load_at(&mut data, 60, 100, &proof[60..]).unwrap();
+        assert!(!cached(&data).is_complete());
+        load_at(&mut data, 0, 100, &proof[..30]).unwrap();
+        assert!(!cached(&data).is_complete());
+        load_at(&mut data, 30, 100, &proof[30..60]).unwrap();
+        assert!(cached(&data).is_complete());
+        assert_eq!(proof, cached(&data).proof);
+
+        let cache = deserialize(&data);
+        assert_eq!(proof, cache.cache);
+        assert_eq!(100, cache.total_len);
+        assert_eq!(100, cache.received_len);
+        assert_eq!([vec![0xff; 12], vec![0x0f]].concat(), cache.received);
+    }
+
+    #[test]
+    fn test_retried_and_overlapping_chunks() {
+        let proof = proof();
+        let mut data = new_account();
+
+        load_at(&mut data, 0, 100, &proof[..50]).unwrap();
+        load_at(&mut data, 0, 100, &proof[..50]).unwrap();
+        load_at(&mut data, 40, 100, &proof[40..90]).unwrap();
+        assert_eq!(90, deserialize(&data).received_len);
+        assert!(!cached(&data).is_complete());
+
+        load_at(&mut data, 90, 100, &proof[90..]).unwrap();
+        assert!(cached(&data).is_complete());
+        assert_eq!(proof, cached(&data).proof);
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let proof = proof();
+        let mut data = new_account();
+
+        assert_eq!(
+            Err(ErrorCode::InvalidProofLength.into()),
+            load_at(&mut data, 0, 0, &proof)
+        );
+        assert_eq!(
+            Err(ErrorCode::InvalidProofLength.into()),
+            load_at(&mut data, 0, MAX_PROOF_LEN as u32 + 1, &proof)
+        );
+
+        load_at(&mut data, 0, 100, &proof[..10]).unwrap();
+        assert_eq!(
+            Err(ErrorCode::ProofLengthMismatch.into()),
+            load_at(&mut data, 10, 101, &proof[10..20])
+        );
+        assert_eq!(
+            Err(ErrorCode::ChunkOutOfBounds.into()),
+            load_at(&mut data, 95, 100, &proof[..10])
+        );
+        assert_eq!(
+            Err(ErrorCode::ChunkOutOfBounds.into()),
+            load_at(&mut data, u32::MAX, 100, &proof[..10])
+        );
+        assert_eq!(Err(ErrorCode::MixedProofLoading.into()), append(&mut data, &proof));
+
+        // the account must be grown first
+        let layout = CacheLayout::read(&data).unwrap();
+        assert!(layout.load_at(&mut data[..50], 10, 100, &proof[10..20]).is_err());
+    }
+
+    #[test]
+    fn test_expected_hash() {
+        let proof = proof();
+        let mut data = new_account();
+
+        set_expected_hash(&mut data, keccak::hash(&proof).0);
+        load_at(&mut data, 0, 100, &proof[..50]).unwrap();
+        assert_eq!(Err(ErrorCode::ProofIncomplete.into()), cached(&data).check_ready());
+
+        // the hash can't be changed once the proof is loading
+        let layout = CacheLayout::read(&data).unwrap();
+        assert_eq!(
+            Err(ErrorCode::ProofAlreadyLoading.into()),
+            layout.set_expected_hash(&mut data, [1; 32])
+        );
+
+        // somebody else overwrites part of the proof
+        load_at(&mut data, 50, 100, &[0; 50]).unwrap();
+        assert_eq!(Err(ErrorCode::ProofHashMismatch.into()), cached(&data).check_ready());
+
+        load_at(&mut data, 50, 100, &proof[50..]).unwrap();
+        assert_eq!(Ok(()), cached(&data).check_ready());
+        assert_eq!(Some(keccak::hash(&proof).0), deserialize(&data).expected_hash);
+
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        assert_eq!(None, deserialize(&data).expected_hash);
+
+        // proofs loaded by appending chunks keep it too
+        set_expected_hash(&mut data, keccak::hash(&proof).0);
+        for chunk in proof.chunks(30) {
+            append(&mut data, chunk).unwrap();
+        }
+        assert_eq!(Ok(()), cached(&data).check_ready());
+        let layout = CacheLayout::read(&data).unwrap();
+        assert_eq!(
+            Err(ErrorCode::ProofAlreadyLoading.into()),
+            layout.set_expected_hash(&mut data, [1; 32])
+        );
+    }
+
+    #[test]
+    fn test_large_proof() {
+        // proofs can be larger than an account can grow in one instruction
+        let proof: Vec<u8> = (0..20 * 1024).map(|i| i as u8).collect();
+        let mut data = new_account();
+
+        for (i, chunk) in proof.chunks(900).enumerate().rev() {
+            load_at(&mut data, i as u32 * 900, proof.len() as u32, chunk).unwrap();
+        }
+        assert!(cached(&data).is_complete());
+        assert_eq!(proof, cached(&data).proof);
+        assert_eq!(ProofCacheAccount::space_for(proof.len()), data.len());
+
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        for chunk in proof.chunks(900) {
+            append(&mut data, chunk).unwrap();
+        }
+        assert_eq!(proof, cached(&data).proof);
+    }
+
+    #[test]
+    fn test_space() {
+        assert_eq!(
+            DISCRIMINATOR_SIZE + ProofCacheAccount::INIT_SPACE,
+            ProofCacheAccount::space_for(DEFAULT_PROOF_CAPACITY)
+        );
+
+        let proof = proof();
+        let mut data = new_account();
+        set_expected_hash(&mut data, [1; 32]);
+        append(&mut data, &proof).unwrap();
+        assert_eq!(
+            deserialize(&data).try_to_vec().unwrap().len() + DISCRIMINATOR_SIZE,
+            CacheLayout::read(&data).unwrap().space()
+        );
+
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        load_at(&mut data, 0, 100, &proof[..10]).unwrap();
+        assert_eq!(
+            ProofCacheAccount::space_for(100),
+            CacheLayout::read(&data).unwrap().space_with_expected_hash()
+        );
+    }
+
+    #[test]
+    fn test_cached_proof() {
+        let proof = proof();
+        let mut data = new_account();
+
+        set_expected_hash(&mut data, [1; 32]);
+        load_at(&mut data, 0, 100, &proof[..50]).unwrap();
+        assert_eq!(Err(ErrorCode::ProofIncomplete.into()), cached(&data).check_ready());
+
+        load_at(&mut data, 50, 100, &proof[50..]).unwrap();
+        assert_eq!(proof, cached(&data).proof);
+        assert_eq!(Err(ErrorCode::ProofHashMismatch.into()), cached(&data).check_ready());
+
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        append(&mut data, &proof).unwrap();
+        assert_eq!(proof, cached(&data).proof);
+        assert_eq!(Ok(()), cached(&data).check_ready());
+
+        assert!(CachedProof::from_account_data(&data[..50]).is_err());
+        assert!(CachedProof::from_account_data(&[0; 100]).is_err());
+    }
+
+    #[test]
+    fn test_clear_account_data() {
+        let proof = proof();
+        let mut data = new_account();
+        set_expected_hash(&mut data, [1; 32]);
+        load_at(&mut data, 0, 100, &proof).unwrap();
+
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        assert_eq!(
+            ProofCacheAccount::default().try_to_vec().unwrap(),
+            deserialize(&data).try_to_vec().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let proof = proof();
+        let mut data = new_account();
+
+        append(&mut data, &proof[..50]).unwrap();
+        append(&mut data, &proof[50..]).unwrap();
+        assert!(cached(&data).is_complete());
+        assert_eq!(proof, deserialize(&data).cache);
+        assert_eq!(
+            Err(ErrorCode::MixedProofLoading.into()),
+            load_at(&mut data, 0, 100, &proof)
+        );
+
+        assert_eq!(
+            Err(ErrorCode::InvalidProofLength.into()),
+            append(&mut data, &vec![0; MAX_PROOF_LEN])
+        );
+
+        // once cleared, any loading method can be used again
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        load_at(&mut data, 0, 100, &proof).unwrap();
+        assert!(cached(&data).is_complete());
+    }
+
+    #[test]
+    fn test_legacy_cache() {
+        // older versions only stored the proof, followed by leftovers of previous proofs
+        let proof = proof();
+        let mut data = ProofCacheAccount::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(&proof);
+        data.extend((0..=255u8).cycle().take(3012 - data.len()));
+
+        assert_eq!(proof, cached(&data).proof);
+        assert_eq!(Ok(()), cached(&data).check_ready());
+
+        // a full cache has nothing behind the proof
+        let mut full = ProofCacheAccount::DISCRIMINATOR.to_vec();
+        full.extend_from_slice(&3000u32.to_le_bytes());
+        full.extend_from_slice(&[7; 3000]);
+        assert_eq!(&[7; 3000], cached(&full).proof);
+
+        // loading more writes the current layout
+        append(&mut data, &proof).unwrap();
+        assert_eq!([proof.clone(), proof.clone()].concat(), deserialize(&data).cache);
+
+        // and once cleared, it's like any other cache
+        ProofCacheAccount::clear_account_data(&mut data).unwrap();
+        load_at(&mut data, 0, 100, &proof).unwrap();
+        assert_eq!(proof, cached(&data).proof);
+    }
+}
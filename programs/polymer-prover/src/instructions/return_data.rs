@@ -0,0 +1,127 @@
+use crate::{ErrorCode, ValidationResultAccount};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{keccak, program::get_return_data};
+
+/// Compact version of the validation result, set as the return data of every validation so CPI
+/// callers don't have to reload the result account. The unindexed data can be too large for the
+/// return data, so only its hash is included. The full data is still in the result account
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, PartialEq, Default)]
+pub struct ValidationReturn {
+    pub is_valid: bool,
+
+    /// one of the `ErrorCode` values if the proof is not valid, zero otherwise
+    pub error_code: u16,
+
+    pub chain_id: u32,
+    pub emitting_contract: [u8; 20],
+    pub topics: Vec<u8>,
+
+    /// keccak256 of the unindexed data, or zeros if the proof is not valid
+    pub unindexed_data_hash: [u8; 32],
+}
+
+impl From<&ValidationResultAccount> for ValidationReturn {
+    fn from(result: &ValidationResultAccount) -> Self {
+        if !result.is_valid {
+            return ValidationReturn {
+                error_code: result.error_code,
+                ..Default::default()
+            };
+        }
+
+        ValidationReturn {
+            is_valid: true,
+            error_code: 0,
+            chain_id: result.chain_id,
+            emitting_contract: result.emitting_contract,
+            topics: result.topics.clone(),
+            unindexed_data_hash: keccak::hash(&result.unindexed_data).0,
+        }
+    }
+}
+
+/// Reads the validation result returned by the prover. Meant to be called right after calling
+/// any of the validate instructions via CPI. Unlike anchor's `Return::get`, it makes sure the
+/// return data was set by the prover and doesn't panic if there's none
+pub fn get_validation_return() -> Result<ValidationReturn> {
+    let (program_id, data) = get_return_data().ok_or(ErrorCode::MissingReturnData)?;
+    require_keys_eq!(program_id, crate::ID, ErrorCode::WrongReturnDataProgram);
+    Ok(ValidationReturn::try_from_slice(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_PROOF_CAPACITY, DISCRIMINATOR_SIZE};
+
+    #[test]
+    fn test_validation_return() {
+        let result = ValidationResultAccount {
+            is_valid: true,
+            chain_id: 10,
+            emitting_contract: [1; 20],
+            topics: vec![2; 64],
+            unindexed_data: b"some data".to_vec(),
+            ..Default::default()
+        };
+
+        let compact = ValidationReturn::from(&result);
+        assert!(compact.is_valid);
+        assert_eq!(10, compact.chain_id);
+        assert_eq!([1; 20], compact.emitting_contract);
+        assert_eq!(vec![2; 64], compact.topics);
+        assert_eq!(keccak::hash(b"some data").0, compact.unindexed_data_hash);
+
+        let invalid = ValidationResultAccount {
+            error_message: "not enough signers: got 1, at least 2 are needed".to_string(),
+            error_code: 6015,
+            chain_id: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ValidationReturn {
+                error_code: 6015,
+                ..Default::default()
+            },
+            ValidationReturn::from(&invalid)
+        );
+    }
+
+    #[test]
+    fn test_result_account_space() {
+        assert_eq!(
+            DISCRIMINATOR_SIZE + ValidationResultAccount::INIT_SPACE,
+            ValidationResultAccount::space_for(DEFAULT_PROOF_CAPACITY)
+        );
+
+        let result = ValidationResultAccount {
+            is_valid: true,
+            topics: vec![2; 64],
+            unindexed_data: vec![3; 5000],
+            ..Default::default()
+        };
+        assert_eq!(result.try_to_vec().unwrap().len() + DISCRIMINATOR_SIZE, result.space());
+    }
+
+    #[test]
+    fn test_result_account_without_error_code() {
+        // result accounts created before the error code was added end right after the data
+        let result = ValidationResultAccount {
+            is_valid: true,
+            chain_id: 10,
+            emitting_contract: [1; 20],
+            topics: vec![2; 64],
+            unindexed_data: vec![3; 100],
+            ..Default::default()
+        };
+        let mut data = ValidationResultAccount::DISCRIMINATOR.to_vec();
+        result.serialize(&mut data).unwrap();
+        data.truncate(data.len() - 2);
+        data.resize(DISCRIMINATOR_SIZE + ValidationResultAccount::INIT_SPACE - 2, 0);
+
+        let decoded = ValidationResultAccount::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(ValidationReturn::from(&result), ValidationReturn::from(&decoded));
+        assert_eq!(result.unindexed_data, decoded.unindexed_data);
+    }
+}
@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use std::fmt;
+
+/// State roots the authority no longer trusts, e.g. after a signer key was compromised. Proofs
+/// against them are rejected even if they are properly signed
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Debug, Clone, Copy, PartialEq)]
+pub enum Revocation {
+    /// a single state root, at whatever peptide height
+    StateRoot { app_hash: [u8; 32] },
+
+    /// every state root between both peptide heights, inclusive
+    HeightRange { from: u64, to: u64 },
+}
+
+impl Revocation {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Revocation::StateRoot { .. } => true,
+            Revocation::HeightRange { from, to } => from <= to,
+        }
+    }
+
+    /// whether this revocation covers the state root at the given height
+    pub fn covers(&self, app_hash: &[u8; 32], peptide_height: u64) -> bool {
+        match self {
+            Revocation::StateRoot { app_hash: revoked } => revoked == app_hash,
+            Revocation::HeightRange { from, to } => (*from..=*to).contains(&peptide_height),
+        }
+    }
+}
+
+impl fmt::Display for Revocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revocation::StateRoot { app_hash } => write!(f, "state root 0x{}", hex::encode(app_hash)),
+            Revocation::HeightRange { from, to } => write!(f, "peptide heights {} to {}", from, to),
+        }
+    }
+}
+
+pub fn is_revoked(revocations: &[Revocation], app_hash: &[u8; 32], peptide_height: u64) -> bool {
+    revocations
+        .iter()
+        .any(|revocation| revocation.covers(app_hash, peptide_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_revoked() {
+        let revocations = [
+            Revocation::StateRoot { app_hash: [1; 32] },
+            Revocation::HeightRange { from: 10, to: 20 },
+        ];
+
+        assert!(is_revoked(&revocations, &[1; 32], 100));
+        assert!(is_revoked(&revocations, &[2; 32], 10));
+        assert!(is_revoked(&revocations, &[2; 32], 20));
+        assert!(!is_revoked(&revocations, &[2; 32], 9));
+        assert!(!is_revoked(&revocations, &[2; 32], 21));
+        assert!(!is_revoked(&[], &[1; 32], 10));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(Revocation::HeightRange { from: 10, to: 10 }.is_valid());
+        assert!(!Revocation::HeightRange { from: 11, to: 10 }.is_valid());
+        assert!(Revocation::StateRoot { app_hash: [0; 32] }.is_valid());
+    }
+}
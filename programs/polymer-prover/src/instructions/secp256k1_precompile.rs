@@ -0,0 +1,147 @@
+use crate::instructions::parse_event::EthAddress;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{secp256k1_program, sysvar::instructions::load_instruction_at_checked};
+
+// layout of the secp256k1 program instruction data. See
+// https://docs.rs/solana-secp256k1-program/2.1.0/solana_secp256k1_program/
+const OFFSETS_START: usize = 1;
+const OFFSETS_SIZE: usize = 11;
+const SIGNATURE_SIZE: usize = 64;
+
+/// half of the secp256k1 curve order, big endian. Signatures with a larger 's' are malleable
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x5d, 0x57, 0x6e,
+    0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Signature checked by the secp256k1 program in the same transaction. If the transaction made it
+/// to the prover, `signer` did sign keccak256(`message`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedSignature {
+    pub signer: EthAddress,
+    pub message: Vec<u8>,
+
+    /// whether the 's' value of the signature is in the upper half of the curve order, which the
+    /// secp256k1 program doesn't reject
+    pub is_high_s: bool,
+}
+
+/// Collects the signatures verified by all the secp256k1 program instructions in the transaction,
+/// read through the instructions sysvar
+pub fn load_verified_signatures(instructions_sysvar: &AccountInfo) -> Result<Vec<VerifiedSignature>> {
+    let mut verified = Vec::new();
+
+    // there's no way to ask for the number of instructions, so go until there are none left
+    for index in 0..=u8::MAX {
+        let Ok(instruction) = load_instruction_at_checked(index.into(), instructions_sysvar) else {
+            break;
+        };
+        if instruction.program_id == secp256k1_program::ID {
+            verified.extend(parse_instruction(&instruction.data, index));
+        }
+    }
+
+    Ok(verified)
+}
+
+/// Parses the signatures out of the data of the secp256k1 program instruction at the given index.
+/// Only signatures whose data is in the instruction itself are returned, the program checks the
+/// rest against other instructions we'd have to load as well
+pub fn parse_instruction(data: &[u8], index: u8) -> Vec<VerifiedSignature> {
+    let count = data.first().copied().unwrap_or_default() as usize;
+
+    (0..count)
+        .filter_map(|i| {
+            let start = OFFSETS_START + i * OFFSETS_SIZE;
+            let offsets = data.get(start..start + OFFSETS_SIZE)?;
+            let u16_at = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]) as usize;
+
+            let (signature_offset, signature_index) = (u16_at(0), offsets[2]);
+            let (address_offset, address_index) = (u16_at(3), offsets[5]);
+            let (message_offset, message_size, message_index) = (u16_at(6), u16_at(8), offsets[10]);
+
+            if [signature_index, address_index, message_index] != [index; 3] {
+                return None;
+            }
+
+            let signature = data.get(signature_offset..signature_offset + SIGNATURE_SIZE)?;
+            let signer = data.get(address_offset..address_offset + 20)?;
+            let message = data.get(message_offset..message_offset + message_size)?;
+
+            Some(VerifiedSignature {
+                signer: EthAddress::from_bytes(signer),
+                message: message.to_vec(),
+                is_high_s: signature[32..] > HALF_ORDER[..],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libsecp256k1::{PublicKey, SecretKey};
+    use sha3::{Digest, Keccak256};
+
+    /// builds the secp256k1 program instruction data the same way solana-secp256k1-program does
+    fn instruction_data(key: &SecretKey, message: &[u8], index: u8) -> (Vec<u8>, EthAddress) {
+        let hash = libsecp256k1::Message::parse(&Keccak256::digest(message).into());
+        let (signature, recovery_id) = libsecp256k1::sign(&hash, key);
+        let pubkey = PublicKey::from_secret_key(key).serialize();
+        let address = EthAddress::from_bytes(&Keccak256::digest(&pubkey[1..])[12..]);
+
+        let address_offset = OFFSETS_START + OFFSETS_SIZE;
+        let signature_offset = address_offset + 20;
+        let message_offset = signature_offset + SIGNATURE_SIZE + 1;
+
+        let mut data = vec![1];
+        data.extend((signature_offset as u16).to_le_bytes());
+        data.push(index);
+        data.extend((address_offset as u16).to_le_bytes());
+        data.push(index);
+        data.extend((message_offset as u16).to_le_bytes());
+        data.extend((message.len() as u16).to_le_bytes());
+        data.push(index);
+        data.extend(address.as_bytes());
+        data.extend(signature.serialize());
+        data.push(recovery_id.serialize());
+        data.extend(message);
+
+        (data, address)
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        let key = SecretKey::parse(&[1; 32]).unwrap();
+        let (data, address) = instruction_data(&key, b"some message", 2);
+
+        assert_eq!(
+            vec![VerifiedSignature {
+                signer: address,
+                message: b"some message".to_vec(),
+                is_high_s: false,
+            }],
+            parse_instruction(&data, 2)
+        );
+
+        // the signature data is in another instruction, so it's not trusted
+        assert!(parse_instruction(&data, 3).is_empty());
+
+        assert!(parse_instruction(&[], 2).is_empty());
+        assert!(parse_instruction(&data[..20], 2).is_empty());
+    }
+
+    #[test]
+    fn test_high_s() {
+        let key = SecretKey::parse(&[1; 32]).unwrap();
+        let (mut data, _) = instruction_data(&key, b"some message", 0);
+
+        // flip s to the other half of the curve order
+        let s_offset = OFFSETS_START + OFFSETS_SIZE + 20 + 32;
+        let mut sig = libsecp256k1::Signature::parse_standard_slice(&data[s_offset - 32..s_offset + 32]).unwrap();
+        sig.s = -sig.s;
+        data[s_offset - 32..s_offset + 32].copy_from_slice(&sig.serialize());
+
+        assert!(parse_instruction(&data, 0)[0].is_high_s);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::instructions::parse_event::{self, EthAddress};
+use crate::ErrorCode;
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{keccak, secp256k1_recover::secp256k1_recover};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -7,6 +8,9 @@ use sha3::Keccak256;
 use std::fmt;
 
 use super::parse_event::EthEvent;
+use super::proof::{MembershipProof, Proof, ProofError, ATTESTATION_SIZE, PROOF_CHECKPOINT};
+use super::revocation::{self, Revocation};
+use super::secp256k1_precompile::VerifiedSignature;
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub enum ValidateEventResult {
@@ -14,12 +18,28 @@ pub enum ValidateEventResult {
 
     InvalidProof(usize, usize),
 
+    UnsupportedProofVersion(u8),
+
+    InvalidEvent(String),
+
     InvalidMembershipProof(String),
 
     InvalidStateRoot(Vec<u8>),
 
     RecoveredInvalidSignerAddress(EthAddress),
 
+    DuplicateSigner(EthAddress),
+
+    NotEnoughSigners(usize, usize),
+
+    StalePeptideHeight(u64, u64),
+
+    StateRootHeightMismatch(u64, u64),
+
+    RevokedStateRoot([u8; 32], u64),
+
+    UnsupportedChain(u32),
+
     Valid(u32, EthEvent),
 }
 
@@ -30,6 +50,14 @@ impl fmt::Display for ValidateEventResult {
                 write!(f, "invalid proof: got {} bytes, at least {} are needed", got, needed)
             }
 
+            ValidateEventResult::UnsupportedProofVersion(version) => {
+                write!(f, "unsupported proof version: {}", version)
+            }
+
+            ValidateEventResult::InvalidEvent(err_msg) => {
+                write!(f, "invalid event: {}", err_msg)
+            }
+
             ValidateEventResult::InvalidSignature(err_msg) => {
                 write!(f, "invalid signature: {}", err_msg)
             }
@@ -46,6 +74,39 @@ impl fmt::Display for ValidateEventResult {
                 write!(f, "recovered invalid signer address: {}", recovered.to_hex())
             }
 
+            ValidateEventResult::DuplicateSigner(signer) => {
+                write!(f, "duplicate signer: {}", signer.to_hex())
+            }
+
+            ValidateEventResult::NotEnoughSigners(got, needed) => {
+                write!(f, "not enough signers: got {}, at least {} are needed", got, needed)
+            }
+
+            ValidateEventResult::StalePeptideHeight(got, min) => {
+                write!(f, "stale peptide height: got {}, at least {} is needed", got, min)
+            }
+
+            ValidateEventResult::RevokedStateRoot(app_hash, peptide_height) => {
+                write!(
+                    f,
+                    "revoked state root: 0x{} at peptide height {}",
+                    hex::encode(app_hash),
+                    peptide_height
+                )
+            }
+
+            ValidateEventResult::UnsupportedChain(chain_id) => {
+                write!(f, "unsupported source chain: {}", chain_id)
+            }
+
+            ValidateEventResult::StateRootHeightMismatch(got, expected) => {
+                write!(
+                    f,
+                    "state root height mismatch: the proof is at {}, the state root at {}",
+                    got, expected
+                )
+            }
+
             ValidateEventResult::Valid(..) => {
                 write!(f, "proof is valid")
             }
@@ -53,79 +114,312 @@ impl fmt::Display for ValidateEventResult {
     }
 }
 
-pub fn handler(
-    proof: &Vec<u8>,
-    client_type: &String,
-    signer_addr: &[u8; 20],
-    peptide_chain_id: u64,
-) -> ValidateEventResult {
-    // first, check there's enough data to read the event_end index
-    let proof_len = proof.len();
-    if proof_len < 128 {
-        return ValidateEventResult::InvalidProof(proof_len, 128);
+impl ValidateEventResult {
+    /// Stable code for the failure, if any. Unlike the message, integrators can branch on it
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            ValidateEventResult::InvalidProof(..) => Some(ErrorCode::ProofTooShort),
+            ValidateEventResult::UnsupportedProofVersion(_) => Some(ErrorCode::UnsupportedProofVersion),
+            ValidateEventResult::InvalidEvent(_) => Some(ErrorCode::InvalidEvent),
+            ValidateEventResult::InvalidSignature(_) => Some(ErrorCode::InvalidSignature),
+            ValidateEventResult::InvalidStateRoot(_) => Some(ErrorCode::InvalidStateRoot),
+            ValidateEventResult::InvalidMembershipProof(_) => Some(ErrorCode::InvalidMembershipProof),
+            ValidateEventResult::RecoveredInvalidSignerAddress(_) => Some(ErrorCode::UnknownSigner),
+            ValidateEventResult::DuplicateSigner(_) => Some(ErrorCode::DuplicateAttestation),
+            ValidateEventResult::NotEnoughSigners(..) => Some(ErrorCode::NotEnoughSigners),
+            ValidateEventResult::StalePeptideHeight(..) => Some(ErrorCode::StalePeptideHeight),
+            ValidateEventResult::StateRootHeightMismatch(..) => Some(ErrorCode::StateRootHeightMismatch),
+            ValidateEventResult::RevokedStateRoot(..) => Some(ErrorCode::RevokedStateRoot),
+            ValidateEventResult::UnsupportedChain(_) => Some(ErrorCode::UnsupportedChain),
+            ValidateEventResult::Valid(..) => None,
+        }
+    }
+}
+
+impl From<ProofError> for ValidateEventResult {
+    fn from(err: ProofError) -> Self {
+        match err {
+            ProofError::UnsupportedVersion(version) => ValidateEventResult::UnsupportedProofVersion(version),
+            ProofError::TooShort(got, needed) => ValidateEventResult::InvalidProof(got, needed),
+            ProofError::InvalidEvent(err_msg) => ValidateEventResult::InvalidEvent(err_msg),
+            ProofError::InvalidMembershipProof(err_msg) => ValidateEventResult::InvalidMembershipProof(err_msg),
+        }
+    }
+}
+
+/// What the state root of a proof is checked against
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateRootPolicy<'a> {
+    /// allowed signers, at least `threshold` distinct ones must sign the state root
+    pub signers: &'a [[u8; 20]],
+    pub threshold: usize,
+    pub peptide_chain_id: u64,
+    /// state roots at lower peptide heights are stale
+    pub min_peptide_height: u64,
+    pub revoked: &'a [Revocation],
+}
+
+impl StateRootPolicy<'_> {
+    fn check_revoked(&self, app_hash: &[u8; 32], peptide_height: u64) -> Option<ValidateEventResult> {
+        revocation::is_revoked(self.revoked, app_hash, peptide_height)
+            .then_some(ValidateEventResult::RevokedStateRoot(*app_hash, peptide_height))
     }
+}
 
-    let event_end: usize = u16::from_be_bytes(<[u8; 2]>::try_from(&proof[126..128]).unwrap()).into();
+/// Validates the proof against the given policy. The state root must be signed by enough of the
+/// allowed signers, fresh and not revoked
+pub fn handler(proof: &[u8], client_type: &str, policy: &StateRootPolicy) -> ValidateEventResult {
+    match Proof::parse(proof) {
+        Ok(proof) => verify_proof(proof, client_type, policy),
+        Err(err) => err.into(),
+    }
+}
+
+/// Same as `handler` but for a proof that was already parsed
+pub fn verify_proof(proof: Proof, client_type: &str, policy: &StateRootPolicy) -> ValidateEventResult {
+    let proof = match verify_parsed(proof, client_type, policy.min_peptide_height) {
+        Ok(proof) => proof,
+        Err(err) => return err,
+    };
 
-    // now, make sure we have enough data to read until the event ends. After it, we have the
-    // membership proof, which will be checked later
-    if proof_len < event_end {
-        return ValidateEventResult::InvalidProof(proof_len, event_end);
+    let peptide_height = proof.peptide_height.to_be_bytes();
+    let attested = proof.attestations.iter().map(|attestation| {
+        recover_signature(
+            policy.peptide_chain_id,
+            &proof.app_hash,
+            &peptide_height,
+            &attestation.signature,
+            attestation.recovery_id,
+        )
+    });
+
+    if let Some(err) = verify_signers(attested, policy.signers, policy.threshold) {
+        return err;
+    }
+    if let Some(err) = policy.check_revoked(&proof.app_hash, proof.peptide_height) {
+        return err;
     }
 
-    let app_hash = &<[u8; 32]>::try_from(&proof[0..32]).unwrap();
+    valid_event(&proof)
+}
+
+/// Same as `handler` but the state root signatures were already checked by the secp256k1 program
+/// in the same transaction, so the ones in the proof are ignored. Only the verified signatures
+/// over this proof's state root count towards the threshold
+pub fn handler_with_verified_signatures(
+    proof: &[u8],
+    client_type: &str,
+    policy: &StateRootPolicy,
+    verified: &[VerifiedSignature],
+) -> ValidateEventResult {
+    match Proof::parse(proof) {
+        Ok(proof) => verify_proof_with_verified_signatures(proof, client_type, policy, verified),
+        Err(err) => err.into(),
+    }
+}
 
-    let recovered = recover_signature(
-        peptide_chain_id,
-        app_hash,
-        &<[u8; 8]>::try_from(&proof[101..109]).unwrap(),
-        &<[u8; 64]>::try_from(&proof[32..96]).unwrap(),
-        proof[96],
+/// Same as `handler_with_verified_signatures` but for a proof that was already parsed
+pub fn verify_proof_with_verified_signatures(
+    proof: Proof,
+    client_type: &str,
+    policy: &StateRootPolicy,
+    verified: &[VerifiedSignature],
+) -> ValidateEventResult {
+    let proof = match verify_parsed(proof, client_type, policy.min_peptide_height) {
+        Ok(proof) => proof,
+        Err(err) => return err,
+    };
+
+    let message = peptide_message(
+        policy.peptide_chain_id,
+        &proof.app_hash,
+        &proof.peptide_height.to_be_bytes(),
     );
-    if let Err(err) = recovered {
-        return ValidateEventResult::InvalidSignature(err.to_string());
+    let attested = verified
+        .iter()
+        .filter(|signature| signature.message == message)
+        .map(|signature| match signature.is_high_s {
+            true => Err("Invalid signature: 's' value is too high".to_string()),
+            false => Ok(signature.signer),
+        });
+
+    if let Some(err) = verify_signers(attested, policy.signers, policy.threshold) {
+        return err;
     }
-    if let Ok(addr) = &recovered {
-        if addr.as_bytes() != signer_addr {
-            return ValidateEventResult::RecoveredInvalidSignerAddress(*addr);
-        }
+    if let Some(err) = policy.check_revoked(&proof.app_hash, proof.peptide_height) {
+        return err;
     }
 
-    let chain_id = u32::from_be_bytes(<[u8; 4]>::try_from(&proof[97..101]).unwrap());
-    let key = format!(
-        "chain/{}/storedLogs/{}/{}/{}/{}",
-        chain_id,
-        client_type,
-        u64::from_be_bytes(<[u8; 8]>::try_from(&proof[109..117]).unwrap()),
-        u32::from_be_bytes(<[u8; 4]>::try_from(&proof[117..121]).unwrap()),
-        u32::from_be_bytes(<[u8; 4]>::try_from(&proof[121..125]).unwrap()),
-    );
+    valid_event(&proof)
+}
+
+/// Same as `handler` but the state root was submitted beforehand with `submit_state_root`, which
+/// checked its signatures already. Checkpoint proofs take the state root from there, while v2
+/// proofs must carry the same one. Their attestations are ignored either way. The state root may
+/// have been revoked after it was submitted
+pub fn handler_with_checkpoint(
+    proof: &[u8],
+    client_type: &str,
+    policy: &StateRootPolicy,
+    state_root: &[u8; 32],
+    state_root_height: u64,
+) -> ValidateEventResult {
+    match Proof::parse(proof) {
+        Ok(proof) => verify_proof_with_checkpoint(proof, client_type, policy, state_root, state_root_height),
+        Err(err) => err.into(),
+    }
+}
+
+/// Same as `handler_with_checkpoint` but for a proof that was already parsed
+pub fn verify_proof_with_checkpoint(
+    proof: Proof,
+    client_type: &str,
+    policy: &StateRootPolicy,
+    state_root: &[u8; 32],
+    state_root_height: u64,
+) -> ValidateEventResult {
+    let mut proof = match check_fresh(proof, policy.min_peptide_height) {
+        Ok(proof) => proof,
+        Err(err) => return err,
+    };
+
+    if proof.peptide_height != state_root_height {
+        return ValidateEventResult::StateRootHeightMismatch(proof.peptide_height, state_root_height);
+    }
+
+    if proof.version == PROOF_CHECKPOINT {
+        proof.app_hash = *state_root;
+    } else if proof.app_hash != *state_root {
+        return ValidateEventResult::InvalidStateRoot(proof.app_hash.to_vec());
+    }
+    if let Some(err) = policy.check_revoked(&proof.app_hash, proof.peptide_height) {
+        return err;
+    }
+
+    if let Err(err) = verify_event_membership(&proof, client_type) {
+        return err;
+    }
+
+    valid_event(&proof)
+}
+
+/// Checks the state root at the given height meets the policy, the same way the one in a proof
+/// does. Each signature is a (signature, recovery id) pair like the attestations in a proof
+pub fn verify_state_root(
+    app_hash: &[u8; 32],
+    peptide_height: u64,
+    signatures: &[[u8; ATTESTATION_SIZE]],
+    policy: &StateRootPolicy,
+) -> Option<ValidateEventResult> {
+    if peptide_height < policy.min_peptide_height {
+        return Some(ValidateEventResult::StalePeptideHeight(
+            peptide_height,
+            policy.min_peptide_height,
+        ));
+    }
+
+    let height = peptide_height.to_be_bytes();
+    let attested = signatures.iter().map(|signature| {
+        recover_signature(
+            policy.peptide_chain_id,
+            app_hash,
+            &height,
+            &<[u8; 64]>::try_from(&signature[..64]).unwrap(),
+            signature[64],
+        )
+    });
+
+    verify_signers(attested, policy.signers, policy.threshold)
+        .or_else(|| policy.check_revoked(app_hash, peptide_height))
+}
+
+/// Checks everything but the signatures. Stale proofs are rejected before doing any hashing
+fn verify_parsed<'a>(
+    proof: Proof<'a>,
+    client_type: &str,
+    min_peptide_height: u64,
+) -> std::result::Result<Proof<'a>, ValidateEventResult> {
+    let proof = check_fresh(proof, min_peptide_height)?;
+
+    // checkpoint proofs don't carry the state root, so they can only be validated against a
+    // submitted one
+    if proof.version == PROOF_CHECKPOINT {
+        return Err(ValidateEventResult::UnsupportedProofVersion(PROOF_CHECKPOINT));
+    }
+
+    verify_event_membership(&proof, client_type)?;
+    Ok(proof)
+}
+
+fn check_fresh(proof: Proof, min_peptide_height: u64) -> std::result::Result<Proof, ValidateEventResult> {
+    if proof.peptide_height < min_peptide_height {
+        return Err(ValidateEventResult::StalePeptideHeight(
+            proof.peptide_height,
+            min_peptide_height,
+        ));
+    }
+
+    Ok(proof)
+}
+
+/// Checks the event is stored under its key in the proof's state root
+fn verify_event_membership(proof: &Proof, client_type: &str) -> std::result::Result<(), ValidateEventResult> {
+    let key = proof.location.key(client_type);
 
     let value = {
         let mut hasher = keccak::Hasher::default();
-        hasher.hash(&proof[128..event_end]);
+        hasher.hash(proof.raw_event);
         hasher.result()
     };
 
-    if let Some(err) = verify_membership(app_hash, key.as_bytes(), &value.0, &proof[event_end..]) {
-        return err;
+    match verify_membership(&proof.app_hash, key.as_bytes(), &value.0, &proof.membership_proof) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the addresses that signed the state root are allowed, distinct and enough
+fn verify_signers(
+    attested: impl Iterator<Item = std::result::Result<EthAddress, String>>,
+    signers: &[[u8; 20]],
+    threshold: usize,
+) -> Option<ValidateEventResult> {
+    let mut recovered_signers: Vec<EthAddress> = Vec::with_capacity(signers.len());
+
+    for addr in attested {
+        let addr = match addr {
+            Ok(addr) => addr,
+            Err(err) => return Some(ValidateEventResult::InvalidSignature(err)),
+        };
+
+        if !signers.contains(addr.as_bytes()) {
+            return Some(ValidateEventResult::RecoveredInvalidSignerAddress(addr));
+        }
+        if recovered_signers.contains(&addr) {
+            return Some(ValidateEventResult::DuplicateSigner(addr));
+        }
+        recovered_signers.push(addr);
     }
 
-    let raw_event = &proof[128..event_end];
-    let num_topics: usize = proof[125].into();
+    if recovered_signers.len() < threshold {
+        return Some(ValidateEventResult::NotEnoughSigners(
+            recovered_signers.len(),
+            threshold,
+        ));
+    }
+
+    None
+}
 
-    let eth_event = parse_event::handler(raw_event, num_topics);
+fn valid_event(proof: &Proof) -> ValidateEventResult {
+    let eth_event = parse_event::handler(proof.raw_event, proof.num_topics);
 
-    ValidateEventResult::Valid(chain_id, eth_event)
+    ValidateEventResult::Valid(proof.location.chain_id, eth_event)
 }
 
-fn recover_signature(
-    peptide_chain_id: u64,
-    app_hash: &[u8; 32],
-    peptide_height: &[u8; 8],
-    signature: &[u8; 64],
-    recovery_id: u8,
-) -> std::result::Result<EthAddress, String> {
+/// Message signed by peptide over the state root at the given height. The signature is over its
+/// keccak256 hash, which is also what the secp256k1 program checks
+pub fn peptide_message(peptide_chain_id: u64, app_hash: &[u8; 32], peptide_height: &[u8; 8]) -> Vec<u8> {
     let message_hash = {
         let mut hasher = keccak::Hasher::default();
         hasher.hash(app_hash);
@@ -133,13 +427,25 @@ fn recover_signature(
         hasher.result()
     };
 
-    let hash = {
-        let mut hasher = keccak::Hasher::default();
-        hasher.hash(&[0; 32]);
-        hasher.hash(&u64_to_32_bytes_array(peptide_chain_id));
-        hasher.hash(&message_hash.0);
-        hasher.result()
-    };
+    let mut message = vec![0; 32];
+    message.extend_from_slice(&u64_to_32_bytes_array(peptide_chain_id));
+    message.extend_from_slice(&message_hash.0);
+    message
+}
+
+/// Hash signed by peptide over the state root at the given height
+pub(crate) fn peptide_message_hash(peptide_chain_id: u64, app_hash: &[u8; 32], peptide_height: &[u8; 8]) -> [u8; 32] {
+    keccak::hash(&peptide_message(peptide_chain_id, app_hash, peptide_height)).0
+}
+
+fn recover_signature(
+    peptide_chain_id: u64,
+    app_hash: &[u8; 32],
+    peptide_height: &[u8; 8],
+    signature: &[u8; 64],
+    recovery_id: u8,
+) -> std::result::Result<EthAddress, String> {
+    let hash = peptide_message_hash(peptide_chain_id, app_hash, peptide_height);
 
     // prevent signature malleability by checking the 's' value
     // https://docs.rs/solana-secp256k1-program/2.1.0/solana_secp256k1_program/#signature-malleability
@@ -148,7 +454,11 @@ fn recover_signature(
         return Err("Invalid signature: 's' value is too high".to_string());
     };
 
-    match secp256k1_recover(&hash.0, recovery_id - 27, signature) {
+    if recovery_id < 27 {
+        return Err(format!("Invalid recovery id: {}", recovery_id));
+    }
+
+    match secp256k1_recover(&hash, recovery_id - 27, signature) {
         Ok(recovered_pubkey) => {
             let recovered_address_hash = Keccak256::digest(recovered_pubkey.to_bytes());
             // take last 20 bytes
@@ -158,24 +468,12 @@ fn recover_signature(
     }
 }
 
-fn verify_membership(app_hash: &[u8; 32], key: &[u8], value: &[u8; 32], proof: &[u8]) -> Option<ValidateEventResult> {
-    let proof_len = proof.len();
-    // first make sure we have enough data to read the start of the first path
-    if proof_len < 2 {
-        return Some(ValidateEventResult::InvalidMembershipProof(
-            "can't read start of first path".to_string(),
-        ));
-    }
-
-    let number_of_paths: usize = proof[0].into();
-    let path_zero_start: usize = proof[1].into();
-
-    if path_zero_start >= proof_len {
-        return Some(ValidateEventResult::InvalidMembershipProof(
-            "can't read first path".to_string(),
-        ));
-    }
-
+fn verify_membership(
+    app_hash: &[u8; 32],
+    key: &[u8],
+    value: &[u8; 32],
+    proof: &MembershipProof,
+) -> Option<ValidateEventResult> {
     let hashed_value = {
         let mut hasher = Sha256::new();
         hasher.update(value);
@@ -184,44 +482,26 @@ fn verify_membership(app_hash: &[u8; 32], key: &[u8], value: &[u8; 32], proof: &
 
     let mut pre_hash = {
         let mut hasher = Sha256::new();
-        hasher.update(&proof[2..path_zero_start]);
+        hasher.update(proof.leaf_prefix);
         hasher.update(key);
         hasher.update([32u8; 1]);
         hasher.update(hashed_value);
         hasher.finalize()
     };
 
-    let mut offset: usize = path_zero_start;
-
-    for _ in 0..number_of_paths {
-        if offset + 1 >= proof_len {
-            return Some(ValidateEventResult::InvalidMembershipProof(
-                "can't read offsets".to_string(),
-            ));
-        }
-
-        let suffix_start: usize = proof[offset].into();
-        let suffix_end: usize = proof[offset + 1].into();
-
-        if offset + suffix_end > proof_len {
-            return Some(ValidateEventResult::InvalidMembershipProof(
-                "can't read path".to_string(),
-            ));
-        }
-
+    for step in proof.path.iter() {
         let mut hasher = Sha256::new();
-        hasher.update(&proof[offset + 2..offset + suffix_start]);
+        hasher.update(step.prefix);
         hasher.update(pre_hash);
-        hasher.update(&proof[offset + suffix_start..offset + suffix_end]);
+        hasher.update(step.suffix);
         pre_hash = hasher.finalize();
-        offset = offset + suffix_end;
     }
 
     if pre_hash.as_slice() != *app_hash {
         return Some(ValidateEventResult::InvalidStateRoot(pre_hash.to_vec()));
     }
 
-    return None;
+    None
 }
 
 fn u64_to_32_bytes_array(input: u64) -> [u8; 32] {
@@ -235,6 +515,7 @@ fn u64_to_32_bytes_array(input: u64) -> [u8; 32] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::instructions::proof::{ATTESTATION_SIZE, PROOF_MAGIC, PROOF_V2};
     use hex;
     use serde::Deserialize;
 
@@ -274,10 +555,19 @@ mod tests {
         }
     }
 
+    fn policy(signers: &[[u8; 20]], threshold: usize) -> StateRootPolicy<'_> {
+        StateRootPolicy {
+            signers,
+            threshold,
+            peptide_chain_id: 901,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_validate_proof_in_one_chunk() {
         let t = setup();
-        let result = handler(&t.proof, &t.client_type, t.signer.as_bytes(), t.peptide_chain_id);
+        let result = handler(&t.proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
 
         let (chain_id, event) = match result {
             ValidateEventResult::Valid(n, t) => (n, t),
@@ -303,12 +593,7 @@ mod tests {
         let t = setup();
 
         for i in 0..(t.proof.len() - 1) {
-            let result = handler(
-                &t.proof[0..i].to_vec(),
-                &t.client_type,
-                t.signer.as_bytes(),
-                t.peptide_chain_id,
-            );
+            let result = handler(&t.proof[0..i], &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
             assert!(!matches!(result, ValidateEventResult::Valid(..)));
         }
     }
@@ -319,7 +604,7 @@ mod tests {
 
         // flip a bit where the signature lives to force recovering a different signer address
         t.proof[35] ^= 1;
-        let result = handler(&t.proof, &t.client_type, t.signer.as_bytes(), t.peptide_chain_id);
+        let result = handler(&t.proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
         println!("result: {}", result);
         assert!(matches!(result, ValidateEventResult::RecoveredInvalidSignerAddress(_)));
     }
@@ -330,11 +615,342 @@ mod tests {
 
         // flip a bit where the membership proof key lives to force an invalid state root error
         t.proof[120] ^= 1;
-        let result = handler(&t.proof, &t.client_type, t.signer.as_bytes(), t.peptide_chain_id);
+        let result = handler(&t.proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
         println!("result: {}", result);
         assert!(matches!(result, ValidateEventResult::InvalidStateRoot(_)));
     }
 
+    #[test]
+    fn test_versioned_proof() {
+        let t = setup();
+
+        let mut versioned = PROOF_MAGIC.to_vec();
+        versioned.push(PROOF_V2);
+        versioned.extend(&t.proof);
+
+        let result = handler(&versioned, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+
+        versioned[PROOF_MAGIC.len()] = 4;
+        let result = handler(&versioned, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert_eq!(ValidateEventResult::UnsupportedProofVersion(4), result);
+    }
+
+    #[test]
+    fn test_stale_peptide_height() {
+        let t = setup();
+        let signers = [*t.signer.as_bytes()];
+        let fresh_from = |min_peptide_height| StateRootPolicy {
+            min_peptide_height,
+            ..policy(&signers, 1)
+        };
+
+        let result = handler(&t.proof, &t.client_type, &fresh_from(3_130_134));
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+
+        let result = handler(&t.proof, &t.client_type, &fresh_from(3_130_135));
+        assert_eq!(ValidateEventResult::StalePeptideHeight(3_130_134, 3_130_135), result);
+
+        let result = handler_with_verified_signatures(&t.proof, &t.client_type, &fresh_from(u64::MAX), &[]);
+        assert_eq!(ValidateEventResult::StalePeptideHeight(3_130_134, u64::MAX), result);
+    }
+
+    #[test]
+    fn test_state_root() {
+        let t = setup();
+        let app_hash = <[u8; 32]>::try_from(&t.proof[0..32]).unwrap();
+        let signature = <[u8; ATTESTATION_SIZE]>::try_from(&t.proof[32..97]).unwrap();
+        let (key0, addr0) = new_signer(0x11);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes()];
+        let verify = |signatures: &[[u8; ATTESTATION_SIZE]], threshold, min_height| {
+            let policy = StateRootPolicy {
+                min_peptide_height: min_height,
+                ..policy(&signers, threshold)
+            };
+            verify_state_root(&app_hash, 3_130_134, signatures, &policy)
+        };
+
+        assert_eq!(None, verify(&[signature], 1, 0));
+        assert_eq!(
+            Some(ValidateEventResult::NotEnoughSigners(1, 2)),
+            verify(&[signature], 2, 0)
+        );
+        assert_eq!(
+            Some(ValidateEventResult::StalePeptideHeight(3_130_134, 3_130_135)),
+            verify(&[signature], 1, 3_130_135)
+        );
+
+        let other = <[u8; ATTESTATION_SIZE]>::try_from(attest(&t, &key0)).unwrap();
+        assert_eq!(None, verify(&[signature, other], 2, 0));
+        assert_eq!(
+            Some(ValidateEventResult::DuplicateSigner(addr0)),
+            verify(&[other, other], 2, 0)
+        );
+    }
+
+    #[test]
+    fn test_validate_with_checkpoint() {
+        let t = setup();
+        let app_hash = <[u8; 32]>::try_from(&t.proof[0..32]).unwrap();
+
+        // the attestation is not checked, only the state root
+        let mut proof = t.proof.clone();
+        proof[35] ^= 1;
+        let policy = policy(&[], 1);
+        let result = handler_with_checkpoint(&proof, &t.client_type, &policy, &app_hash, 3_130_134);
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+
+        let result = handler_with_checkpoint(&proof, &t.client_type, &policy, &[1; 32], 3_130_134);
+        assert_eq!(ValidateEventResult::InvalidStateRoot(app_hash.to_vec()), result);
+
+        let result = handler_with_checkpoint(&proof, &t.client_type, &policy, &app_hash, 3_130_135);
+        assert_eq!(
+            ValidateEventResult::StateRootHeightMismatch(3_130_134, 3_130_135),
+            result
+        );
+
+        let stale = StateRootPolicy {
+            min_peptide_height: 3_130_135,
+            ..policy
+        };
+        let result = handler_with_checkpoint(&proof, &t.client_type, &stale, &app_hash, 3_130_134);
+        assert_eq!(ValidateEventResult::StalePeptideHeight(3_130_134, 3_130_135), result);
+    }
+
+    #[test]
+    fn test_revoked_state_root() {
+        let t = setup();
+        let app_hash = <[u8; 32]>::try_from(&t.proof[0..32]).unwrap();
+        let signers = [*t.signer.as_bytes()];
+        let revoked = |revoked| StateRootPolicy {
+            revoked,
+            ..policy(&signers, 1)
+        };
+
+        let other_root = [Revocation::StateRoot { app_hash: [1; 32] }];
+        let result = handler(&t.proof, &t.client_type, &revoked(&other_root));
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+
+        let same_root = [Revocation::StateRoot { app_hash }];
+        let result = handler(&t.proof, &t.client_type, &revoked(&same_root));
+        assert_eq!(ValidateEventResult::RevokedStateRoot(app_hash, 3_130_134), result);
+
+        let heights = [Revocation::HeightRange {
+            from: 3_130_000,
+            to: 3_130_134,
+        }];
+        let result = handler(&t.proof, &t.client_type, &revoked(&heights));
+        assert_eq!(ValidateEventResult::RevokedStateRoot(app_hash, 3_130_134), result);
+
+        // the state root may be revoked after it was submitted
+        let result = handler_with_checkpoint(&t.proof, &t.client_type, &revoked(&same_root), &app_hash, 3_130_134);
+        assert_eq!(ValidateEventResult::RevokedStateRoot(app_hash, 3_130_134), result);
+
+        let signature = <[u8; ATTESTATION_SIZE]>::try_from(&t.proof[32..97]).unwrap();
+        assert_eq!(
+            Some(ValidateEventResult::RevokedStateRoot(app_hash, 3_130_134)),
+            verify_state_root(&app_hash, 3_130_134, &[signature], &revoked(&heights))
+        );
+    }
+
+    #[test]
+    fn test_multiple_signers() {
+        let t = setup();
+        let (key0, addr0) = new_signer(0x11);
+        let (key1, addr1) = new_signer(0x22);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes(), *addr1.as_bytes()];
+
+        let mut proof = t.proof.clone();
+        proof.extend(attest(&t, &key0));
+
+        // two out of three signers
+        let result = handler(&proof, &t.client_type, &policy(&signers, 2));
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+
+        // all three signers
+        proof.extend(attest(&t, &key1));
+        let result = handler(&proof, &t.client_type, &policy(&signers, 3));
+        assert!(matches!(result, ValidateEventResult::Valid(11_155_420, _)));
+    }
+
+    #[test]
+    fn test_not_enough_signers() {
+        let t = setup();
+        let (key0, addr0) = new_signer(0x11);
+        let (_, addr1) = new_signer(0x22);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes(), *addr1.as_bytes()];
+
+        let result = handler(&t.proof, &t.client_type, &policy(&signers, 2));
+        assert_eq!(ValidateEventResult::NotEnoughSigners(1, 2), result);
+
+        let mut proof = t.proof.clone();
+        proof.extend(attest(&t, &key0));
+        let result = handler(&proof, &t.client_type, &policy(&signers, 3));
+        assert_eq!(ValidateEventResult::NotEnoughSigners(2, 3), result);
+    }
+
+    #[test]
+    fn test_duplicate_signers() {
+        let t = setup();
+        let (key0, addr0) = new_signer(0x11);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes()];
+
+        // the same signature twice
+        let mut proof = t.proof.clone();
+        proof.extend_from_slice(&t.proof[32..97]);
+        let result = handler(&proof, &t.client_type, &policy(&signers, 2));
+        assert_eq!(ValidateEventResult::DuplicateSigner(t.signer), result);
+
+        // a different, but also valid, signature from the same signer
+        let mut proof = t.proof.clone();
+        proof.extend(attest(&t, &key0));
+        proof.extend(attest(&t, &key0));
+        let result = handler(&proof, &t.client_type, &policy(&signers, 2));
+        assert_eq!(ValidateEventResult::DuplicateSigner(addr0), result);
+    }
+
+    #[test]
+    fn test_unknown_extra_signer() {
+        let t = setup();
+        let (key0, addr0) = new_signer(0x11);
+
+        let mut proof = t.proof.clone();
+        proof.extend(attest(&t, &key0));
+        let result = handler(&proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert_eq!(ValidateEventResult::RecoveredInvalidSignerAddress(addr0), result);
+    }
+
+    #[test]
+    fn test_truncated_extra_attestation() {
+        let t = setup();
+        let (key0, addr0) = new_signer(0x11);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes()];
+
+        let mut proof = t.proof.clone();
+        proof.extend(&attest(&t, &key0)[..ATTESTATION_SIZE - 1]);
+        let result = handler(&proof, &t.client_type, &policy(&signers, 2));
+        assert_eq!(ValidateEventResult::InvalidProof(proof.len(), proof.len() + 1), result);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut t = setup();
+        let valid = handler(&t.proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert!(valid.error_code().is_none());
+
+        t.proof[120] ^= 1;
+        let result = handler(&t.proof, &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert_eq!(
+            Some(ErrorCode::InvalidStateRoot.into()),
+            result.error_code().map(u32::from)
+        );
+
+        let result = handler(&t.proof[..100], &t.client_type, &policy(&[*t.signer.as_bytes()], 1));
+        assert_eq!(
+            Some(ErrorCode::ProofTooShort.into()),
+            result.error_code().map(u32::from)
+        );
+
+        // every failure has its own code
+        let failures = [
+            ValidateEventResult::InvalidSignature(String::new()),
+            ValidateEventResult::InvalidProof(0, 0),
+            ValidateEventResult::UnsupportedProofVersion(0),
+            ValidateEventResult::InvalidEvent(String::new()),
+            ValidateEventResult::InvalidMembershipProof(String::new()),
+            ValidateEventResult::InvalidStateRoot(vec![]),
+            ValidateEventResult::RecoveredInvalidSignerAddress(EthAddress::default()),
+            ValidateEventResult::DuplicateSigner(EthAddress::default()),
+            ValidateEventResult::NotEnoughSigners(0, 0),
+            ValidateEventResult::StalePeptideHeight(0, 0),
+            ValidateEventResult::StateRootHeightMismatch(0, 0),
+            ValidateEventResult::RevokedStateRoot([0; 32], 0),
+            ValidateEventResult::UnsupportedChain(0),
+        ];
+        let mut codes: Vec<u32> = failures.iter().map(|f| u32::from(f.error_code().unwrap())).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(failures.len(), codes.len());
+    }
+
+    fn new_signer(seed: u8) -> (libsecp256k1::SecretKey, EthAddress) {
+        let key = libsecp256k1::SecretKey::parse(&[seed; 32]).unwrap();
+        let pubkey = libsecp256k1::PublicKey::from_secret_key(&key);
+        let hash = Keccak256::digest(&pubkey.serialize()[1..]);
+        (key, EthAddress::from_bytes(&hash[12..32]))
+    }
+
+    #[test]
+    fn test_verified_signatures() {
+        let t = setup();
+        let (_, addr0) = new_signer(0x11);
+        let signers = [*t.signer.as_bytes(), *addr0.as_bytes()];
+        let message = peptide_message(
+            t.peptide_chain_id,
+            &<[u8; 32]>::try_from(&t.proof[0..32]).unwrap(),
+            &<[u8; 8]>::try_from(&t.proof[101..109]).unwrap(),
+        );
+        assert_eq!(
+            keccak::hash(&message).0,
+            peptide_message_hash(
+                t.peptide_chain_id,
+                &<[u8; 32]>::try_from(&t.proof[0..32]).unwrap(),
+                &<[u8; 8]>::try_from(&t.proof[101..109]).unwrap(),
+            )
+        );
+
+        let verified = |signer: EthAddress, message: &[u8]| VerifiedSignature {
+            signer,
+            message: message.to_vec(),
+            is_high_s: false,
+        };
+        let validate = |verified: &[VerifiedSignature], threshold| {
+            handler_with_verified_signatures(&t.proof, &t.client_type, &policy(&signers, threshold), verified)
+        };
+
+        // the attestation in the proof doesn't count, only the verified signatures do
+        assert_eq!(ValidateEventResult::NotEnoughSigners(0, 1), validate(&[], 1));
+
+        let both = [verified(t.signer, &message), verified(addr0, &message)];
+        assert!(matches!(validate(&both, 2), ValidateEventResult::Valid(11_155_420, _)));
+
+        // signatures over other messages are ignored
+        let other = [verified(t.signer, &message), verified(addr0, b"other message")];
+        assert_eq!(ValidateEventResult::NotEnoughSigners(1, 2), validate(&other, 2));
+
+        let (_, unknown) = new_signer(0x33);
+        assert_eq!(
+            ValidateEventResult::RecoveredInvalidSignerAddress(unknown),
+            validate(&[verified(unknown, &message)], 1)
+        );
+        assert_eq!(
+            ValidateEventResult::DuplicateSigner(t.signer),
+            validate(&[verified(t.signer, &message), verified(t.signer, &message)], 1)
+        );
+
+        let high_s = VerifiedSignature {
+            is_high_s: true,
+            ..verified(t.signer, &message)
+        };
+        assert!(matches!(
+            validate(&[high_s], 1),
+            ValidateEventResult::InvalidSignature(_)
+        ));
+    }
+
+    /// signs the state root of the test proof and returns the (signature, recovery id) pair
+    fn attest(t: &TestContext, key: &libsecp256k1::SecretKey) -> Vec<u8> {
+        let hash = peptide_message_hash(
+            t.peptide_chain_id,
+            &<[u8; 32]>::try_from(&t.proof[0..32]).unwrap(),
+            &<[u8; 8]>::try_from(&t.proof[101..109]).unwrap(),
+        );
+        let (sig, recovery_id) = libsecp256k1::sign(&libsecp256k1::Message::parse(&hash), key);
+        let mut attestation = sig.serialize().to_vec();
+        attestation.push(recovery_id.serialize() + 27);
+        attestation
+    }
+
     fn read_and_decode_proof_file(file_path: &str) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
         let contents = std::fs::read_to_string(file_path).expect("could not read hex file");
         let decoded = hex::decode(&contents.trim().as_bytes()[2..])?;
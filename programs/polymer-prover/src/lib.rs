@@ -1,14 +1,42 @@
 #![allow(unexpected_cfgs)]
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use anchor_lang::system_program;
 use borsh::BorshDeserialize;
 
 pub mod instructions;
+#[cfg(any(test, feature = "proof-builder"))]
+pub mod testing;
 
+use instructions::proof::{EventLocation, Proof};
+use instructions::proof_cache::{CacheLayout, CachedProof};
+pub use instructions::return_data::{get_validation_return, ValidationReturn};
+use instructions::revocation::Revocation;
+use instructions::secp256k1_precompile::{self as secp256k1_precompile, VerifiedSignature};
+use instructions::validate_event::{StateRootPolicy, ValidateEventResult};
 use instructions::*;
 
 const DISCRIMINATOR_SIZE: usize = 8;
 
+const MAX_CLIENT_TYPE_LEN: usize = 32;
+
+const MAX_SIGNERS: usize = 8;
+
+const MAX_REVOCATIONS: usize = 32;
+
+const MAX_CHAIN_LABEL_LEN: usize = 32;
+
+/// proof size the cache and result accounts are created for. They grow on demand past it
+const DEFAULT_PROOF_CAPACITY: usize = 3000;
+
+/// proofs are loaded and read in place, but validating one copies the event out of it, so the
+/// program heap (32 KiB) still bounds how large proofs can be. Accounts could hold far more
+const MAX_PROOF_LEN: usize = 24 * 1024;
+
+/// one bit per proof byte, see ProofCacheAccount.received
+const RECEIVED_BITMAP_LEN: usize = DEFAULT_PROOF_CAPACITY.div_ceil(8);
+
 // This program ID is used when deploying the program to solana mainnet and used from our
 // testnet and mainnet envs.
 // For devnet and shadownet, we use FtdxWoZXZKNYn1Dx9XXDE5hKXWf69tjFJUofNZuaWUH3
@@ -46,7 +74,7 @@ pub struct InternalAccount {
     pub authority: Pubkey,
 
     /// Client type used on peptide to generate the proof. It is part of the proof key
-    #[max_len(32)]
+    #[max_len(MAX_CLIENT_TYPE_LEN)]
     pub client_type: String,
 
     /// Known signer address that signed the peptide state root
@@ -54,246 +82,2448 @@ pub struct InternalAccount {
 
     // Peptide chain ID included in the proof
     pub peptide_chain_id: u64,
+
+    /// Set of signers allowed to sign the peptide state root. When empty, `signer_addr` is the
+    /// only allowed signer
+    #[max_len(MAX_SIGNERS)]
+    pub signers: Vec<[u8; 20]>,
+
+    /// Number of distinct signers from `signers` that must sign the peptide state root
+    pub threshold: u8,
+
+    /// Authority proposed by the current one. It becomes the authority once it accepts the role
+    pub pending_authority: Option<Pubkey>,
+
+    /// Highest peptide height of all the proofs accepted so far
+    pub latest_peptide_height: u64,
+
+    /// Proofs more than this many peptide blocks behind `latest_peptide_height` are rejected.
+    /// Zero disables the check
+    pub max_peptide_height_lag: u64,
+
+    /// Proofs below this peptide height are rejected, to cut off old state roots
+    pub min_peptide_height: u64,
+
+    /// While set, proofs can't be validated, nor loaded by callers that pass this account, see
+    /// pause
+    pub paused: bool,
+
+    /// Number of revocations in the revocations account. validate_event can't leave that account
+    /// out while there are any
+    pub revocation_count: u8,
+
+    /// Set when the first source chain is configured, and never reset. Until then proofs from any
+    /// chain are accepted under `client_type`, as they were before source chains could be
+    /// configured
+    pub chain_configs_enforced: bool,
+}
+
+impl InternalAccount {
+    /// Returns the allowed signers along with how many of them must sign the state root
+    pub fn signer_set(&self) -> (&[[u8; 20]], usize) {
+        if self.signers.is_empty() {
+            (std::slice::from_ref(&self.signer_addr), 1)
+        } else {
+            (&self.signers, self.threshold.into())
+        }
+    }
+
+    /// Returns the lowest peptide height a proof must have to be accepted
+    pub fn min_accepted_height(&self) -> u64 {
+        let lag_limit = match self.max_peptide_height_lag {
+            0 => 0,
+            lag => self.latest_peptide_height.saturating_sub(lag),
+        };
+        self.min_peptide_height.max(lag_limit)
+    }
+
+    /// Returns what state roots are checked against, given the revoked ones
+    pub fn policy<'a>(&'a self, revoked: &'a [Revocation]) -> StateRootPolicy<'a> {
+        let (signers, threshold) = self.signer_set();
+        StateRootPolicy {
+            signers,
+            threshold,
+            peptide_chain_id: self.peptide_chain_id,
+            min_peptide_height: self.min_accepted_height(),
+            revoked,
+        }
+    }
+
+    /// Returns the client type the proof's event is stored under on peptide, or the failure if
+    /// its source chain is not configured or disabled
+    pub fn client_type_for<'a>(
+        &'a self,
+        proof: &Proof,
+        chain: Option<&'a ChainConfigAccount>,
+    ) -> std::result::Result<&'a str, ValidateEventResult> {
+        match chain {
+            Some(chain) if chain.chain_id == proof.location.chain_id && chain.enabled => {
+                Ok(chain.client_type.as_deref().unwrap_or(&self.client_type))
+            }
+            None if !self.chain_configs_enforced => Ok(&self.client_type),
+            _ => Err(ValidateEventResult::UnsupportedChain(proof.location.chain_id)),
+        }
+    }
+
+    /// Validates the proof against the config stored in this account, the revoked state roots
+    /// and the config of its source chain
+    pub fn validate_proof(
+        &mut self,
+        proof: &[u8],
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+    ) -> ValidateEventResult {
+        self.validate_with_location(proof, revoked, chain).0
+    }
+
+    /// same as validate_proof but also returns the location of the event, if the proof could be
+    /// parsed
+    pub fn validate_with_location(
+        &mut self,
+        proof: &[u8],
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+    ) -> (ValidateEventResult, Option<EventLocation>) {
+        self.validate_parsed(proof, revoked, chain, validate_event::verify_proof)
+    }
+
+    /// validates the proof in the cache account data, without copying it
+    pub fn validate_cached_proof(
+        &mut self,
+        cache: &AccountInfo,
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+    ) -> Result<ValidateEventResult> {
+        with_cached_proof(cache, |proof| self.validate_proof(proof, revoked, chain))
+    }
+
+    /// same as validate_proof but the state root signatures were checked by the secp256k1 program
+    pub fn validate_proof_with_verified_signatures(
+        &mut self,
+        proof: &[u8],
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+        verified: &[VerifiedSignature],
+    ) -> ValidateEventResult {
+        let verify = |proof: Proof, client_type: &str, policy: &StateRootPolicy| {
+            validate_event::verify_proof_with_verified_signatures(proof, client_type, policy, verified)
+        };
+        self.validate_parsed(proof, revoked, chain, verify).0
+    }
+
+    /// same as validate_proof but against a state root submitted with submit_state_root, whose
+    /// signatures were already checked
+    pub fn validate_proof_with_state_root(
+        &mut self,
+        proof: &[u8],
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+        state_root: &StateRootAccount,
+    ) -> ValidateEventResult {
+        let verify = |proof: Proof, client_type: &str, policy: &StateRootPolicy| {
+            validate_event::verify_proof_with_checkpoint(
+                proof,
+                client_type,
+                policy,
+                &state_root.app_hash,
+                state_root.peptide_height,
+            )
+        };
+        self.validate_parsed(proof, revoked, chain, verify).0
+    }
+
+    /// parses the proof, only once, and checks it with `verify` under the client type of its
+    /// source chain. Moves `latest_peptide_height` forward if the proof was accepted
+    fn validate_parsed(
+        &mut self,
+        proof: &[u8],
+        revoked: &[Revocation],
+        chain: Option<&ChainConfigAccount>,
+        verify: impl FnOnce(Proof, &str, &StateRootPolicy) -> ValidateEventResult,
+    ) -> (ValidateEventResult, Option<EventLocation>) {
+        let proof = match Proof::parse(proof) {
+            Ok(proof) => proof,
+            Err(err) => return (err.into(), None),
+        };
+        let (location, peptide_height) = (proof.location, proof.peptide_height);
+
+        let result = match self.client_type_for(&proof, chain) {
+            Ok(client_type) => verify(proof, client_type, &self.policy(revoked)),
+            Err(err) => err,
+        };
+        if let ValidateEventResult::Valid(..) = result {
+            self.latest_peptide_height = self.latest_peptide_height.max(peptide_height);
+        }
+        (result, Some(location))
+    }
+}
+
+/// Source chain proofs are accepted from, at the `[b"chain", chain ID (big endian)]` PDA. Once the
+/// first one is configured, proofs from chains without one are rejected
+#[account]
+#[derive(InitSpace)]
+pub struct ChainConfigAccount {
+    pub chain_id: u32,
+
+    /// disabled chains are rejected like unconfigured ones, but keep their config
+    pub enabled: bool,
+
+    /// human readable name of the chain, e.g. "Optimism Sepolia"
+    #[max_len(MAX_CHAIN_LABEL_LEN)]
+    pub label: String,
+
+    /// client type the chain's events are stored under on peptide, if it's not the one in the
+    /// internal account
+    #[max_len(MAX_CLIENT_TYPE_LEN)]
+    pub client_type: Option<String>,
+}
+
+impl ChainConfigAccount {
+    /// Reads the config passed by the caller, if the account is one. Its seeds can't be checked
+    /// before the proof is parsed, so its chain ID is checked against the proof's instead, see
+    /// InternalAccount::client_type_for
+    pub fn load(account: &AccountInfo) -> Result<Option<ChainConfigAccount>> {
+        if account.owner != &crate::ID {
+            return Ok(None);
+        }
+        let data = account.try_borrow_data()?;
+        Ok(Some(ChainConfigAccount::try_deserialize(&mut &data[..])?))
+    }
+
+    /// same as load, for instructions where the account is optional
+    pub fn load_optional(account: Option<&AccountInfo>) -> Result<Option<ChainConfigAccount>> {
+        Ok(account.map(Self::load).transpose()?.flatten())
+    }
 }
 
 #[derive(Accounts)]
-pub struct ValidateEvent<'info> {
-    // user will be the owner of the pda account
+#[instruction(chain_id: u32)]
+pub struct UpdateChainConfig<'info> {
+    /// only the authority set during initialization can configure source chains
     #[account(mut, signer)]
     pub authority: Signer<'info>,
 
-    /// CHECK: the proof lives in this account. It would have been loaded into via the
-    /// LoadProof instruction
+    /// chain configs are enforced from the first one on, see InternalAccount.chain_configs_enforced
     #[account(
         mut,
-        seeds = [b"cache", authority.key().as_ref()],
+        seeds = [b"internal"],
         bump,
+        has_one = authority @ ErrorCode::Unauthorized,
     )]
-    pub cache_account: Account<'info, ProofCacheAccount>,
+    pub internal: Account<'info, InternalAccount>,
 
-    /// CHECK: the result of the validation will be stored in this account.
+    /// created the first time the chain is configured
     #[account(
-        mut,
-        seeds = [b"result", authority.key().as_ref()],
+        init_if_needed,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + ChainConfigAccount::INIT_SPACE,
+        seeds = [b"chain", chain_id.to_be_bytes().as_ref()],
         bump,
     )]
-    pub result_account: Account<'info, ValidationResultAccount>,
+    pub chain_config: Account<'info, ChainConfigAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(chain_id: u32)]
+pub struct RemoveChainConfig<'info> {
+    /// only the authority set during initialization can remove source chains. It gets the rent back
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
 
-    /// CHECK: we need to access the internal account to get the client type and signer address,
-    /// which are unique to the program instance and required to validate the proof
     #[account(
         seeds = [b"internal"],
         bump,
+        has_one = authority @ ErrorCode::Unauthorized,
     )]
     pub internal: Account<'info, InternalAccount>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"chain", chain_id.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub chain_config: Account<'info, ChainConfigAccount>,
+}
+
+/// emitted every time the authority configures a source chain
+#[event]
+pub struct ChainConfigUpdated {
+    pub chain_id: u32,
+    pub enabled: bool,
+    pub label: String,
+    pub client_type: Option<String>,
+}
+
+/// emitted when the authority removes the config of a source chain
+#[event]
+pub struct ChainConfigRemoved {
+    pub chain_id: u32,
 }
 
+/// State roots revoked by the authority, checked by every validate instruction
 #[account]
 #[derive(InitSpace)]
-pub struct ProofCacheAccount {
-    #[max_len(3000)]
-    pub cache: Vec<u8>,
+pub struct RevocationsAccount {
+    #[max_len(MAX_REVOCATIONS)]
+    pub revocations: Vec<Revocation>,
 }
 
-#[account]
-#[derive(InitSpace, Default)]
-pub struct ValidationResultAccount {
-    /// whether the proof is valid or not
-    pub is_valid: bool,
+impl RevocationsAccount {
+    /// Reads the revocations from the account, which is only created when the first state root
+    /// is revoked. The seeds are checked by the caller
+    pub fn load(account: &AccountInfo) -> Result<Vec<Revocation>> {
+        if account.owner != &crate::ID {
+            return Ok(Vec::new());
+        }
+        let data = account.try_borrow_data()?;
+        Ok(RevocationsAccount::try_deserialize(&mut &data[..])?.revocations)
+    }
 
-    /// error message if the proof is not valid
-    #[max_len(64)]
-    pub error_message: String,
+    /// Same as load, for instructions where the account is optional so callers of older versions
+    /// keep working. It can only be left out while nothing is revoked
+    pub fn load_optional(account: Option<&AccountInfo>, internal: &InternalAccount) -> Result<Vec<Revocation>> {
+        match account {
+            Some(account) => Self::load(account),
+            None => {
+                require!(internal.revocation_count == 0, ErrorCode::RevocationsAccountRequired);
+                Ok(Vec::new())
+            }
+        }
+    }
+}
 
-    /// the chain ID of the event that was validated
-    pub chain_id: u32,
+#[derive(Accounts)]
+pub struct UpdateRevocations<'info> {
+    /// only the authority set during initialization can revoke state roots
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
 
-    /// the emitting contract address that emitted the event
-    pub emitting_contract: [u8; 20],
+    /// keeps track of the number of revocations, see InternalAccount.revocation_count
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
 
-    #[max_len(32 * 4)] // 32 bytes per topic, max 4 topics
-    pub topics: Vec<u8>,
+    /// created with the first revocation
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + RevocationsAccount::INIT_SPACE,
+        seeds = [b"revocations"],
+        bump,
+    )]
+    pub revocations: Account<'info, RevocationsAccount>,
 
-    /// the unindexed data of the event that was validated
-    #[max_len(3000)]
-    pub unindexed_data: Vec<u8>,
+    pub system_program: Program<'info, System>,
+}
+
+/// emitted when the authority revokes a state root, or a range of them
+#[event]
+pub struct RevocationAdded {
+    pub revocation: Revocation,
+}
+
+/// emitted when the authority lifts a revocation
+#[event]
+pub struct RevocationRemoved {
+    pub revocation: Revocation,
+}
+
+/// Peptide state root whose signatures were checked by submit_state_root. Proofs at the same
+/// peptide height can be validated against it without checking any signature
+#[account]
+#[derive(InitSpace)]
+pub struct StateRootAccount {
+    pub app_hash: [u8; 32],
+    pub peptide_height: u64,
+
+    /// paid for the account and gets the rent back when it's closed, see close_state_root
+    pub submitter: Pubkey,
 }
 
 #[derive(Accounts)]
-pub struct CreateAccounts<'info> {
-    // user will be the owner of the pda accounts
+#[instruction(app_hash: [u8; 32], peptide_height: u64)]
+pub struct SubmitStateRoot<'info> {
+    /// anyone can submit a state root as long as it is signed by the signers. They pay for the
+    /// state root account
     #[account(mut, signer)]
-    pub authority: Signer<'info>,
+    pub payer: Signer<'info>,
 
-    /// CHECK: this is used to store the chunks of the proof to be validated later. We have to do
-    /// it this way because the proof is too large to be passed as an argument to the instruction
-    /// itself thanks to the transaction size limit.
+    /// there's a single state root per peptide height
     #[account(
         init,
-        seeds = [b"cache", authority.key().as_ref()],
+        payer = payer,
+        space = DISCRIMINATOR_SIZE + StateRootAccount::INIT_SPACE,
+        seeds = [b"state_root", peptide_height.to_be_bytes().as_ref()],
         bump,
-        payer = authority,
-        space = DISCRIMINATOR_SIZE + ProofCacheAccount::INIT_SPACE,
     )]
-    pub cache_account: Account<'info, ProofCacheAccount>,
+    pub state_root: Account<'info, StateRootAccount>,
 
-    /// CHECK: this is used to store the result of the validation. We have to do it this way
-    /// because the result may be too large to be emitted as an event or through the return data
+    /// CHECK: same as ValidateEvent.internal
     #[account(
-        init,
-        seeds = [b"result", authority.key().as_ref()],
+        mut,
+        seeds = [b"internal"],
         bump,
-        payer = authority,
-        space = DISCRIMINATOR_SIZE + ValidationResultAccount::INIT_SPACE,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
     )]
-    pub result_account: Account<'info, ValidationResultAccount>,
+    pub internal: Account<'info, InternalAccount>,
+
+    /// CHECK: same as ValidateEvent.revocations
+    #[account(
+        seeds = [b"revocations"],
+        bump,
+    )]
+    pub revocations: UncheckedAccount<'info>,
 
-    // need this to create the pda account
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CloseAccounts<'info> {
-    // user will be the owner of the pda accounts
+pub struct ValidateEventWithCheckpoint<'info> {
+    // user will be the owner of the pda account
     #[account(mut, signer)]
     pub authority: Signer<'info>,
 
-    /// CHECK: close the cache account and transfer its lamports to the authority
+    /// CHECK: same as ValidateEvent.cache_account
     #[account(
         mut,
-        close = authority,
+        owner = crate::ID,
         seeds = [b"cache", authority.key().as_ref()],
         bump,
     )]
-    pub cache_account: Account<'info, ProofCacheAccount>,
+    pub cache_account: UncheckedAccount<'info>,
 
-    /// CHECK: close the result account and transfer its lamports to the authority
+    /// CHECK: same as ValidateEvent.result_account
     #[account(
         mut,
-        close = authority,
+        owner = crate::ID,
         seeds = [b"result", authority.key().as_ref()],
         bump,
     )]
-    pub result_account: Account<'info, ValidationResultAccount>,
+    pub result_account: UncheckedAccount<'info>,
+
+    /// the program config the proof is validated against
+    pub config: ValidationConfig<'info>,
+
+    /// state root at the peptide height of the proof. Only submit_state_root creates these
+    /// accounts, so there's no need to check the seeds
+    pub state_root: Account<'info, StateRootAccount>,
+
+    // need this to grow the result account
+    pub system_program: Program<'info, System>,
+}
+
+/// emitted when a state root is submitted
+#[event]
+pub struct StateRootSubmitted {
+    pub app_hash: [u8; 32],
+    pub peptide_height: u64,
 }
 
 #[derive(Accounts)]
-pub struct LoadProof<'info> {
-    /// user will be the owner of the pda account
-    #[account(mut, signer)]
+#[instruction(peptide_height: u64)]
+pub struct CloseStateRoot<'info> {
+    /// either the submitter of the state root or, once it can't be used anymore, the authority set
+    /// during initialization
+    #[account(signer)]
     pub authority: Signer<'info>,
 
-    /// CHECK: here's where the proof chunks are stored
     #[account(
         mut,
-        seeds = [b"cache", authority.key().as_ref()],
+        seeds = [b"state_root", peptide_height.to_be_bytes().as_ref()],
         bump,
+        has_one = submitter,
+        close = submitter,
     )]
-    pub cache_account: Account<'info, ProofCacheAccount>,
-}
+    pub state_root: Account<'info, StateRootAccount>,
 
-#[derive(Accounts)]
-pub struct ClearProofCache<'info> {
-    /// user will be the owner of the pda account
-    #[account(mut, signer)]
-    pub authority: Signer<'info>,
+    /// CHECK: gets the rent back. It must be the one stored in the state root account
+    #[account(mut)]
+    pub submitter: UncheckedAccount<'info>,
 
-    /// CHECK: this is used to store the chunks of the proof to be validated later. We have to do
+    /// holds the authority and the height limits
     #[account(
-        mut ,
-        seeds = [b"cache", authority.key().as_ref()],
+        seeds = [b"internal"],
         bump,
     )]
-    pub cache_account: Account<'info, ProofCacheAccount>,
+    pub internal: Account<'info, InternalAccount>,
 
-    // need this to mutate the pda account
-    pub system_program: Program<'info, System>,
+    /// CHECK: same as ValidateEvent.revocations
+    #[account(
+        seeds = [b"revocations"],
+        bump,
+    )]
+    pub revocations: UncheckedAccount<'info>,
 }
 
-#[program]
-pub mod polymer_prover {
-
-    use instructions::validate_event::ValidateEventResult;
-
-    use crate::instructions::parse_event::EthAddress;
-
-    use super::*;
-
-    pub fn initialize(
-        ctx: Context<Initialize>,
-        client_type: String,
-        signer_addr: [u8; 20],
-        peptide_chain_id: u64,
-    ) -> Result<()> {
-        let internal = &mut ctx.accounts.internal;
+/// emitted when a state root is closed
+#[event]
+pub struct StateRootClosed {
+    pub app_hash: [u8; 32],
+    pub peptide_height: u64,
+}
 
-        internal.authority = ctx.accounts.authority.key();
-        internal.client_type = client_type;
-        internal.signer_addr = signer_addr;
-        internal.peptide_chain_id = peptide_chain_id;
+/// runs `f` on the proof in the cache account, borrowed straight from the account data. Fails if
+/// the proof isn't ready to be validated
+fn with_cached_proof<T>(cache: &AccountInfo, f: impl FnOnce(&[u8]) -> T) -> Result<T> {
+    let data = cache.try_borrow_data()?;
+    let cached = CachedProof::from_account_data(&data)?;
+    cached.check_ready()?;
+    Ok(f(cached.proof))
+}
 
-        msg!("client_type: {}", internal.client_type);
-        msg!("peptide_chain_id: {}", internal.peptide_chain_id);
-        msg!(
-            "signer_addr: {}",
-            EthAddress::from_bytes(&internal.signer_addr).to_hex()
-        );
-        Ok(())
+/// writes the latest peptide height back to the internal account, if it was passed writable, see
+/// ValidateEvent.internal
+fn save_latest_peptide_height(internal: &Account<InternalAccount>) -> Result<()> {
+    if internal.to_account_info().is_writable {
+        internal.exit(&crate::ID)?;
     }
+    Ok(())
+}
 
-    pub fn create_accounts(_ctx: Context<CreateAccounts>) -> Result<()> {
-        msg!("accounts successfully created");
-        Ok(())
-    }
+/// stores the result in the result account, growing it if needed, and clears the cache account.
+/// Both are written in place, without deserializing them first
+fn store_result<'info>(
+    result: ValidateEventResult,
+    cache: &AccountInfo<'info>,
+    result_account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system: Option<&Program<'info, System>>,
+) -> Result<ValidationReturn> {
+    require!(
+        result_account
+            .try_borrow_data()?
+            .starts_with(ValidationResultAccount::DISCRIMINATOR),
+        anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+    );
 
-    pub fn close_accounts(_ctx: Context<CloseAccounts>) -> Result<()> {
-        msg!("accounts successfully closed");
-        Ok(())
-    }
+    let result = ValidationResultAccount::from(result);
+    grow_account(result_account.clone(), result.space(), payer, system)?;
+    result.try_serialize(&mut &mut result_account.try_borrow_mut_data()?[..])?;
 
-    pub fn clear_proof_cache(ctx: Context<ClearProofCache>) -> Result<()> {
-        msg!("proof cache successfully cleared");
-        ctx.accounts.cache_account.cache.clear();
-        Ok(())
-    }
+    ProofCacheAccount::clear_account_data(&mut cache.try_borrow_mut_data()?)?;
 
-    pub fn load_proof(ctx: Context<LoadProof>, proof_chunk: Vec<u8>) -> Result<()> {
-        ctx.accounts.cache_account.cache.extend(proof_chunk.iter());
-        Ok(())
-    }
+    Ok(ValidationReturn::from(&result))
+}
 
-    pub fn validate_event(ctx: Context<ValidateEvent>) -> Result<()> {
-        // this is set by the owner/deployer during initialize()
-        let internal = &ctx.accounts.internal;
+#[derive(Accounts)]
+pub struct MigrateInternal<'info> {
+    /// only the authority can migrate the internal account. It pays for the extra rent
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
 
-        let result = validate_event::handler(
-            &ctx.accounts.cache_account.cache,
-            &internal.client_type,
-            &internal.signer_addr,
-            internal.peptide_chain_id,
-        );
+    /// CHECK: internal accounts created by older versions of the program are too small to hold
+    /// the fields added since, so they can't be deserialized until they are grown. The
+    /// authority is checked against the raw account data instead, see migrate_internal
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"internal"],
+        bump,
+    )]
+    pub internal: UncheckedAccount<'info>,
 
-        msg!("{}", result);
+    // need this to grow the internal account
+    pub system_program: Program<'info, System>,
+}
 
-        // create a new ValidationResultAccount to store the result of the validation
-        let mut out = ValidationResultAccount::default();
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// only the authority set during initialization can update the config
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
 
-        // if the result is valid, we store the event data in the result account
-        // if the result is invalid, we store the error message in the result account
+    /// holds the config that is going to be updated
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSigners<'info> {
+    /// only the authority set during initialization can update the signers
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// holds the signer set that is going to be updated
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateHeightLimits<'info> {
+    /// only the authority set during initialization can update the height limits
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// holds the height limits that are going to be updated
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePause<'info> {
+    /// only the authority set during initialization can pause the prover
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// holds the pause flag that is going to be updated
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    /// only the current authority can propose a new one
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// the proposed authority is stored here until it accepts the role
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    /// must match the authority proposed by the current one
+    #[account(signer)]
+    pub pending_authority: Signer<'info>,
+
+    /// the authority is transferred here
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        constraint = internal.pending_authority == Some(pending_authority.key()) @ ErrorCode::NotPendingAuthority,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+}
+
+/// emitted when the current authority proposes a new one
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+/// emitted when the proposed authority accepts the role
+#[event]
+pub struct AuthorityTransferred {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+/// emitted every time the signer set in the internal account is updated
+#[event]
+pub struct SignersUpdated {
+    pub old_signers: Vec<[u8; 20]>,
+    pub new_signers: Vec<[u8; 20]>,
+    pub old_threshold: u8,
+    pub new_threshold: u8,
+}
+
+/// emitted every time the peptide height limits in the internal account are updated
+#[event]
+pub struct HeightLimitsUpdated {
+    pub old_max_peptide_height_lag: u64,
+    pub new_max_peptide_height_lag: u64,
+    pub old_min_peptide_height: u64,
+    pub new_min_peptide_height: u64,
+}
+
+/// emitted when the authority pauses or unpauses the prover
+#[event]
+pub struct PauseUpdated {
+    pub paused: bool,
+}
+
+/// emitted every time the config in the internal account is updated
+#[event]
+pub struct ConfigUpdated {
+    pub old_client_type: String,
+    pub new_client_type: String,
+    pub old_signer_addr: [u8; 20],
+    pub new_signer_addr: [u8; 20],
+    pub old_peptide_chain_id: u64,
+    pub new_peptide_chain_id: u64,
+}
+
+/// emitted by validate_event_inline, which has no result account to store the result in
+#[event]
+pub struct EventValidated {
+    pub result: ValidationReturn,
+}
+
+/// Accounts the proof is validated against, shared by the validate instructions. validate_event
+/// takes them on its own, see ValidateEvent
+#[derive(Accounts)]
+pub struct ValidationConfig<'info> {
+    /// CHECK: we need to access the internal account to get the client type and signer address,
+    /// which are unique to the program instance and required to validate the proof. It's mutable
+    /// to keep track of the latest peptide height
+    #[account(
+        mut,
+        seeds = [b"internal"],
+        bump,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+
+    /// CHECK: same as ValidateEvent.revocations
+    #[account(
+        seeds = [b"revocations"],
+        bump,
+    )]
+    pub revocations: UncheckedAccount<'info>,
+
+    /// CHECK: same as ValidateEvent.chain_config
+    pub chain_config: UncheckedAccount<'info>,
+}
+
+impl ValidationConfig<'_> {
+    /// reads the revoked state roots and the config of the proof's source chain
+    pub fn load(&self) -> Result<(Vec<Revocation>, Option<ChainConfigAccount>)> {
+        Ok((
+            RevocationsAccount::load(&self.revocations)?,
+            ChainConfigAccount::load(&self.chain_config)?,
+        ))
+    }
+}
+
+#[derive(Accounts)]
+pub struct ValidateEvent<'info> {
+    // user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the proof lives in this account. It would have been loaded into via the
+    /// LoadProof instruction. It's read in place instead of deserialized, see CachedProof
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: the result of the validation will be stored in this account.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    /// CHECK: we need to access the internal account to get the client type and signer address,
+    /// which are unique to the program instance and required to validate the proof. Pass it
+    /// writable to keep track of the latest peptide height. Callers of older versions pass it
+    /// read-only, which still works but doesn't record the height
+    #[account(
+        seeds = [b"internal"],
+        bump,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub internal: Account<'info, InternalAccount>,
+
+    // the accounts below were added later, so they are optional for callers of older versions to
+    // keep working. They can be left out, or replaced by the program ID to skip one of them
+    /// CHECK: state roots revoked by the authority. The account only exists once the first one
+    /// is revoked, see RevocationsAccount::load. It can only be left out while nothing is revoked
+    #[account(
+        seeds = [b"revocations"],
+        bump,
+    )]
+    pub revocations: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: config of the proof's source chain, at the `[b"chain", chain ID (big endian)]` PDA,
+    /// see ChainConfigAccount::load. Proofs are rejected without it once chain configs are
+    /// enforced, see InternalAccount.chain_configs_enforced
+    pub chain_config: Option<UncheckedAccount<'info>>,
+
+    // need this to grow the result account
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct ValidateEventPrecompiled<'info> {
+    // user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as ValidateEvent.cache_account
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: same as ValidateEvent.result_account
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    /// the program config the proof is validated against
+    pub config: ValidationConfig<'info>,
+
+    /// CHECK: the secp256k1 program instructions that verified the state root signatures are read
+    /// from here
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    // need this to grow the result account
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ProofCacheAccount {
+    #[max_len(DEFAULT_PROOF_CAPACITY)]
+    pub cache: Vec<u8>,
+
+    /// length of the proof being loaded with load_proof_at. Zero if it's loaded with load_proof
+    pub total_len: u32,
+
+    /// which bytes of the proof were loaded so far with load_proof_at, one bit each
+    #[max_len(RECEIVED_BITMAP_LEN)]
+    pub received: Vec<u8>,
+
+    /// number of bits set in `received`
+    pub received_len: u32,
+
+    /// keccak256 the loaded proof must hash to, if set with set_expected_proof_hash
+    pub expected_hash: Option<[u8; 32]>,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ValidationResultAccount {
+    /// whether the proof is valid or not
+    pub is_valid: bool,
+
+    /// error message if the proof is not valid
+    #[max_len(64)]
+    pub error_message: String,
+
+    /// the chain ID of the event that was validated
+    pub chain_id: u32,
+
+    /// the emitting contract address that emitted the event
+    pub emitting_contract: [u8; 20],
+
+    #[max_len(32 * 4)] // 32 bytes per topic, max 4 topics
+    pub topics: Vec<u8>,
+
+    /// the unindexed data of the event that was validated
+    #[max_len(DEFAULT_PROOF_CAPACITY)]
+    pub unindexed_data: Vec<u8>,
+
+    /// one of the `ErrorCode` values if the proof is not valid, zero otherwise. It comes last so
+    /// the accounts keep the layout of the versions without it, but results stored by those
+    /// versions don't have a meaningful one
+    pub error_code: u16,
+}
+
+impl ValidationResultAccount {
+    /// account size needed for events with up to `capacity` bytes of unindexed data
+    pub fn space_for(capacity: usize) -> usize {
+        DISCRIMINATOR_SIZE + Self::INIT_SPACE - DEFAULT_PROOF_CAPACITY + capacity
+    }
+
+    /// account size needed to store the current contents
+    pub fn space(&self) -> usize {
+        DISCRIMINATOR_SIZE
+            + 1
+            + 4
+            + self.error_message.len()
+            + 4
+            + 20
+            + 4
+            + self.topics.len()
+            + 4
+            + self.unindexed_data.len()
+            + 2
+    }
+}
+
+impl From<ValidateEventResult> for ValidationResultAccount {
+    fn from(result: ValidateEventResult) -> Self {
+        let mut out = ValidationResultAccount::default();
+
+        // if the result is valid, we store the event data in the result account
+        // if the result is invalid, we store the error message in the result account
         if let ValidateEventResult::Valid(chain_id, event) = result {
             out.is_valid = true;
             out.chain_id = chain_id;
             out.emitting_contract = *event.emitting_contract.as_bytes();
-            out.topics = event.topics.clone();
-            out.unindexed_data = event.unindexed_data.clone();
+            out.topics = event.topics;
+            out.unindexed_data = event.unindexed_data;
         } else {
             out.is_valid = false;
             out.error_message = result.to_string();
+            out.error_code = result.error_code().map_or(0, |code| u32::from(code) as u16);
         }
 
-        ctx.accounts.result_account.set_inner(out);
-        ctx.accounts.cache_account.cache.clear();
-
-        Ok(())
+        out
     }
 }
+
+#[derive(Accounts)]
+#[instruction(chain_id: u32, block_number: u64, tx_index: u32, log_index: u32)]
+pub struct ValidateEventOnce<'info> {
+    // user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the proof lives in this account. It would have been loaded into via the
+    /// LoadProof instruction. It's read in place instead of deserialized, see CachedProof
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: the result of the validation will be stored in this account.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    /// the program config the proof is validated against
+    pub config: ValidationConfig<'info>,
+
+    /// CHECK: marks the event as consumed, see ConsumedEventAccount. It is unique per event so a
+    /// second attempt to consume the same event finds it already marked, no matter who sends the
+    /// proof. It's only created once the proof is found valid, so invalid proofs cost no rent
+    #[account(
+        mut,
+        seeds = [
+            b"consumed",
+            chain_id.to_be_bytes().as_ref(),
+            block_number.to_be_bytes().as_ref(),
+            tx_index.to_be_bytes().as_ref(),
+            log_index.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub consumed_event: UncheckedAccount<'info>,
+
+    // need this to create the consumed event account
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ConsumedEventAccount {
+    /// whether the event has been successfully validated already
+    pub is_consumed: bool,
+
+    /// the authority that consumed the event
+    pub consumed_by: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct CreateAccounts<'info> {
+    // user will be the owner of the pda accounts
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: this is used to store the chunks of the proof to be validated later. We have to do
+    /// it this way because the proof is too large to be passed as an argument to the instruction
+    /// itself thanks to the transaction size limit.
+    #[account(
+        init,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + ProofCacheAccount::INIT_SPACE,
+    )]
+    pub cache_account: Account<'info, ProofCacheAccount>,
+
+    /// CHECK: this is used to store the result of the validation. We have to do it this way
+    /// because the result may be too large to be emitted as an event or through the return data
+    #[account(
+        init,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + ValidationResultAccount::INIT_SPACE,
+    )]
+    pub result_account: Account<'info, ValidationResultAccount>,
+
+    // need this to create the pda account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(capacity: u32)]
+pub struct CreateAccountsWithCapacity<'info> {
+    // user will be the owner of the pda accounts
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as CreateAccounts.cache_account, sized for proofs of `capacity` bytes. Accounts
+    /// can't be created larger than 10 KiB, so larger ones are grown afterwards
+    #[account(
+        init,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = ProofCacheAccount::space_for(capacity as usize).min(MAX_PERMITTED_DATA_INCREASE),
+    )]
+    pub cache_account: Account<'info, ProofCacheAccount>,
+
+    /// CHECK: same as CreateAccounts.result_account, sized for events of up to `capacity` bytes
+    #[account(
+        init,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = ValidationResultAccount::space_for(capacity as usize).min(MAX_PERMITTED_DATA_INCREASE),
+    )]
+    pub result_account: Account<'info, ValidationResultAccount>,
+
+    // need this to create the pda account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GrowAccounts<'info> {
+    /// user will be the owner of the pda accounts and pays for the extra rent
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the cache account to grow. Its contents are left as they are
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: the result account to grow. Its contents are left as they are
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    // need this to transfer the rent difference
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseAccounts<'info> {
+    // user will be the owner of the pda accounts
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: close the cache account and transfer its lamports to the authority. It's not
+    /// deserialized, so accounts written by older versions of the program can be closed too
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: close the result account and transfer its lamports to the authority. Same as the
+    /// cache account
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LoadProof<'info> {
+    /// user will be the owner of the pda account and pays for it to grow if needed
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: here's where the proof chunks are stored. They are written in place instead of
+    /// deserializing the whole account, see CacheLayout
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // need this to grow the pda account. It's optional so callers that never grow the cache can
+    // keep sending just the two accounts above
+    pub system_program: Option<Program<'info, System>>,
+
+    /// loading proofs is not allowed while the prover is paused. It comes last and is optional so
+    /// callers of older versions, which don't pass it, keep working
+    #[account(
+        seeds = [b"internal"],
+        bump,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub internal: Option<Account<'info, InternalAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct SetExpectedProofHash<'info> {
+    /// pays for the cache to grow if it has no room for the hash
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the hash is stored along with the proof, see CacheLayout
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // only needed if the cache has to grow, same as LoadProof.system_program
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[derive(Accounts)]
+pub struct ClearProofCache<'info> {
+    /// user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: this is used to store the chunks of the proof to be validated later. It's cleared
+    /// in place, see ProofCacheAccount::clear_account_data
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // need this to mutate the pda account
+    pub system_program: Program<'info, System>,
+}
+
+/// seed that tells apart the cache and result accounts of the same authority. Slot 0 has no seed,
+/// so its accounts are the same ones used by the instructions that don't take a slot
+pub fn slot_seed(slot: u16) -> Vec<u8> {
+    if slot == 0 {
+        vec![]
+    } else {
+        slot.to_be_bytes().to_vec()
+    }
+}
+
+/// grows the account so it can hold `space` bytes, with the payer covering the extra rent. Accounts
+/// are never shrunk. The system program is only needed if the account is short of rent
+fn grow_account<'info>(
+    account: AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system: Option<&Program<'info, System>>,
+) -> Result<()> {
+    if account.data_len() >= space {
+        return Ok(());
+    }
+    require!(
+        space <= account.data_len() + MAX_PERMITTED_DATA_INCREASE,
+        ErrorCode::AccountTooSmall
+    );
+
+    let missing_rent = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        let Some(system) = system else {
+            msg!("the system program is needed to grow account {}", account.key());
+            return err!(anchor_lang::error::ErrorCode::AccountNotEnoughKeys);
+        };
+        let accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        system_program::transfer(CpiContext::new(system.to_account_info(), accounts), missing_rent)?;
+    }
+
+    account.realloc(space, false)?;
+    Ok(())
+}
+
+/// appends the chunk to the proof in the cache account, growing the account first if needed
+fn append_chunk<'info>(
+    cache: &AccountInfo<'info>,
+    chunk: &[u8],
+    payer: &Signer<'info>,
+    system: Option<&Program<'info, System>>,
+) -> Result<()> {
+    let layout = CacheLayout::read(&cache.try_borrow_data()?)?;
+    grow_account(cache.clone(), layout.space_to_append(chunk.len())?, payer, system)?;
+    layout.append(&mut cache.try_borrow_mut_data()?, chunk)
+}
+
+/// writes the chunk at the given offset of the proof in the cache account, growing the account
+/// first if needed
+fn load_chunk_at<'info>(
+    cache: &AccountInfo<'info>,
+    offset: u32,
+    total_len: u32,
+    chunk: &[u8],
+    payer: &Signer<'info>,
+    system: Option<&Program<'info, System>>,
+) -> Result<()> {
+    let layout = CacheLayout::read(&cache.try_borrow_data()?)?;
+    grow_account(cache.clone(), layout.space_to_load(total_len)?, payer, system)?;
+    layout.load_at(&mut cache.try_borrow_mut_data()?, offset, total_len, chunk)
+}
+
+/// grows the cache and result accounts towards what's needed for proofs of `capacity` bytes, by
+/// at most 10 KiB each
+fn grow_accounts_for<'info>(
+    cache: AccountInfo<'info>,
+    result: AccountInfo<'info>,
+    capacity: u32,
+    payer: &Signer<'info>,
+    system: &Program<'info, System>,
+) -> Result<()> {
+    require!(capacity as usize <= MAX_PROOF_LEN, ErrorCode::InvalidProofLength);
+
+    let targets = [
+        (cache, ProofCacheAccount::space_for(capacity as usize)),
+        (result, ValidationResultAccount::space_for(capacity as usize)),
+    ];
+    for (account, space) in targets {
+        let space = space.min(account.data_len() + MAX_PERMITTED_DATA_INCREASE);
+        grow_account(account.clone(), space, payer, Some(system))?;
+        msg!("account {} is {} bytes", account.key(), account.data_len());
+    }
+    Ok(())
+}
+
+/// sets the hash the proof in the cache account must match, growing the account first if needed
+fn set_expected_hash<'info>(
+    cache: &AccountInfo<'info>,
+    expected_hash: [u8; 32],
+    payer: &Signer<'info>,
+    system: Option<&Program<'info, System>>,
+) -> Result<()> {
+    let layout = CacheLayout::read(&cache.try_borrow_data()?)?;
+    grow_account(cache.clone(), layout.space_with_expected_hash(), payer, system)?;
+    layout.set_expected_hash(&mut cache.try_borrow_mut_data()?, expected_hash)
+}
+
+/// creates the PDA with the given seeds, owned by this program, like anchor's `init` constraint.
+/// Lamports sent to its address beforehand count towards its rent, so that can't block it
+fn create_pda<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system: &Program<'info, System>,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let signer = &[seeds];
+    let system = system.to_account_info();
+
+    if account.lamports() == 0 {
+        let accounts = system_program::CreateAccount {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        return system_program::create_account(
+            CpiContext::new_with_signer(system, accounts, signer),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let missing_rent = rent.saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        let accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        system_program::transfer(CpiContext::new(system.clone(), accounts), missing_rent)?;
+    }
+    let accounts = system_program::Allocate {
+        account_to_allocate: account.clone(),
+    };
+    system_program::allocate(
+        CpiContext::new_with_signer(system.clone(), accounts, signer),
+        space as u64,
+    )?;
+    let accounts = system_program::Assign {
+        account_to_assign: account.clone(),
+    };
+    system_program::assign(CpiContext::new_with_signer(system, accounts, signer), &crate::ID)
+}
+
+/// closes the account like anchor's `close` constraint, without deserializing it first
+fn close_account<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? += lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+
+    account.assign(&system_program::ID);
+    account.realloc(0, false)?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct CreateAccountsInSlot<'info> {
+    // user will be the owner of the pda accounts
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as CreateAccounts.cache_account, one per slot
+    #[account(
+        init,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + ProofCacheAccount::INIT_SPACE,
+    )]
+    pub cache_account: Account<'info, ProofCacheAccount>,
+
+    /// CHECK: same as CreateAccounts.result_account, one per slot
+    #[account(
+        init,
+        seeds = [b"result", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + ValidationResultAccount::INIT_SPACE,
+    )]
+    pub result_account: Account<'info, ValidationResultAccount>,
+
+    // need this to create the pda account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct CloseAccountsInSlot<'info> {
+    // user will be the owner of the pda accounts
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: close the cache account and transfer its lamports to the authority. It's not
+    /// deserialized, so accounts written by older versions of the program can be closed too
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: close the result account and transfer its lamports to the authority. Same as the
+    /// cache account
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct LoadProofInSlot<'info> {
+    /// user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as LoadProof.cache_account, one per slot
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // need this to grow the pda account
+    pub system_program: Program<'info, System>,
+
+    /// same as LoadProof.internal
+    #[account(
+        seeds = [b"internal"],
+        bump,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub internal: Option<Account<'info, InternalAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct ClearProofCacheInSlot<'info> {
+    /// user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as ClearProofCache.cache_account, one per slot
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct GrowAccountsInSlot<'info> {
+    /// user will be the owner of the pda accounts and pays for the extra rent
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as GrowAccounts.cache_account, one per slot
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: same as GrowAccounts.result_account, one per slot
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    // need this to transfer the rent difference
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct SetExpectedProofHashInSlot<'info> {
+    /// pays for the cache to grow if it has no room for the hash
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: same as SetExpectedProofHash.cache_account, one per slot
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // need this to grow the pda account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(slot: u16)]
+pub struct ValidateEventInSlot<'info> {
+    // user will be the owner of the pda account
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the proof lives in this account. It would have been loaded into via the
+    /// LoadProofInSlot instruction. It's read in place instead of deserialized, see CachedProof
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    /// CHECK: the result of the validation will be stored in this account.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"result", authority.key().as_ref(), slot_seed(slot).as_ref()],
+        bump,
+    )]
+    pub result_account: UncheckedAccount<'info>,
+
+    /// the program config the proof is validated against
+    pub config: ValidationConfig<'info>,
+
+    // need this to grow the result account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResizeCache<'info> {
+    /// user will be the owner of the pda account and pays for the extra rent
+    #[account(mut, signer)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: cache accounts created by older versions of the program may be smaller than what's
+    /// currently required. This grows (or shrinks) them to the current size, but never below
+    /// what's needed for the proof being loaded. It's read in place, see CacheLayout. Only slot 0
+    /// has such accounts, since older versions didn't have slots, so there's no slot variant
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"cache", authority.key().as_ref()],
+        bump,
+    )]
+    pub cache_account: UncheckedAccount<'info>,
+
+    // need this to transfer the rent difference
+    pub system_program: Program<'info, System>,
+
+    /// same as LoadProof.internal
+    #[account(
+        seeds = [b"internal"],
+        bump,
+        constraint = !internal.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub internal: Option<Account<'info, InternalAccount>>,
+}
+
+#[program]
+pub mod polymer_prover {
+
+    use crate::instructions::parse_event::EthAddress;
+
+    use super::*;
+
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        client_type: String,
+        signer_addr: [u8; 20],
+        peptide_chain_id: u64,
+    ) -> Result<()> {
+        let internal = &mut ctx.accounts.internal;
+
+        internal.authority = ctx.accounts.authority.key();
+        internal.client_type = client_type;
+        internal.signer_addr = signer_addr;
+        internal.peptide_chain_id = peptide_chain_id;
+
+        msg!("client_type: {}", internal.client_type);
+        msg!("peptide_chain_id: {}", internal.peptide_chain_id);
+        msg!(
+            "signer_addr: {}",
+            EthAddress::from_bytes(&internal.signer_addr).to_hex()
+        );
+        Ok(())
+    }
+
+    /// grows an internal account created by an older version of the program to the current size.
+    /// The fields added since are read as zero, empty or unset. It has to be called once after
+    /// upgrading the program, before any other instruction using the internal account
+    pub fn migrate_internal(ctx: Context<MigrateInternal>) -> Result<()> {
+        let internal = ctx.accounts.internal.to_account_info();
+
+        {
+            let data = internal.try_borrow_data()?;
+            require!(
+                data.starts_with(InternalAccount::DISCRIMINATOR),
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            // the authority is the first field, so it's in the same place in every version
+            let authority = data
+                .get(DISCRIMINATOR_SIZE..DISCRIMINATOR_SIZE + 32)
+                .ok_or(anchor_lang::error::ErrorCode::AccountDidNotDeserialize)?;
+            require!(
+                authority == ctx.accounts.authority.key().as_ref(),
+                ErrorCode::Unauthorized
+            );
+        }
+
+        let old_len = internal.data_len();
+        grow_account(
+            internal.clone(),
+            DISCRIMINATOR_SIZE + InternalAccount::INIT_SPACE,
+            &ctx.accounts.authority,
+            Some(&ctx.accounts.system_program),
+        )?;
+        // the new fields must read as zero, whatever realloc left in there
+        internal.try_borrow_mut_data()?[old_len..].fill(0);
+
+        // make sure the account can be used from now on
+        InternalAccount::try_deserialize(&mut &internal.try_borrow_data()?[..])?;
+
+        msg!(
+            "internal account successfully migrated to {} bytes",
+            internal.data_len()
+        );
+        Ok(())
+    }
+
+    /// lets the authority rotate the peptide signer and update the rest of the config without
+    /// having to redeploy the program
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        client_type: String,
+        signer_addr: [u8; 20],
+        peptide_chain_id: u64,
+    ) -> Result<()> {
+        require!(client_type.len() <= MAX_CLIENT_TYPE_LEN, ErrorCode::ClientTypeTooLong);
+
+        let internal = &mut ctx.accounts.internal;
+
+        // the signer address is only used while there's no signer set, so rotating it then would
+        // look like it worked while it changes nothing
+        require!(
+            internal.signers.is_empty() || signer_addr == internal.signer_addr,
+            ErrorCode::SignerSetConfigured
+        );
+
+        emit!(ConfigUpdated {
+            old_client_type: internal.client_type.clone(),
+            new_client_type: client_type.clone(),
+            old_signer_addr: internal.signer_addr,
+            new_signer_addr: signer_addr,
+            old_peptide_chain_id: internal.peptide_chain_id,
+            new_peptide_chain_id: peptide_chain_id,
+        });
+
+        internal.client_type = client_type;
+        internal.signer_addr = signer_addr;
+        internal.peptide_chain_id = peptide_chain_id;
+
+        msg!("client_type: {}", internal.client_type);
+        msg!("peptide_chain_id: {}", internal.peptide_chain_id);
+        msg!(
+            "signer_addr: {}",
+            EthAddress::from_bytes(&internal.signer_addr).to_hex()
+        );
+        msg!("config successfully updated");
+        Ok(())
+    }
+
+    /// replaces the set of signers allowed to sign the peptide state root and the number of them
+    /// that are required for a proof to be valid
+    pub fn update_signers(ctx: Context<UpdateSigners>, signers: Vec<[u8; 20]>, threshold: u8) -> Result<()> {
+        require!(
+            !signers.is_empty() && signers.len() <= MAX_SIGNERS,
+            ErrorCode::InvalidSignerSet
+        );
+        require!(
+            threshold > 0 && usize::from(threshold) <= signers.len(),
+            ErrorCode::InvalidThreshold
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(!signers[..i].contains(signer), ErrorCode::DuplicateSigner);
+        }
+
+        let internal = &mut ctx.accounts.internal;
+
+        emit!(SignersUpdated {
+            old_signers: internal.signers.clone(),
+            new_signers: signers.clone(),
+            old_threshold: internal.threshold,
+            new_threshold: threshold,
+        });
+
+        internal.signers = signers;
+        internal.threshold = threshold;
+
+        for signer in internal.signers.iter() {
+            msg!("signer: {}", EthAddress::from_bytes(signer).to_hex());
+        }
+        msg!("threshold: {}", internal.threshold);
+        msg!("signers successfully updated");
+        Ok(())
+    }
+
+    /// sets the peptide heights proofs are accepted at. Proofs below `min_peptide_height`, or more
+    /// than `max_peptide_height_lag` blocks behind the latest accepted proof, are rejected as
+    /// stale. Zero disables either check
+    pub fn update_height_limits(
+        ctx: Context<UpdateHeightLimits>,
+        max_peptide_height_lag: u64,
+        min_peptide_height: u64,
+    ) -> Result<()> {
+        let internal = &mut ctx.accounts.internal;
+
+        emit!(HeightLimitsUpdated {
+            old_max_peptide_height_lag: internal.max_peptide_height_lag,
+            new_max_peptide_height_lag: max_peptide_height_lag,
+            old_min_peptide_height: internal.min_peptide_height,
+            new_min_peptide_height: min_peptide_height,
+        });
+
+        internal.max_peptide_height_lag = max_peptide_height_lag;
+        internal.min_peptide_height = min_peptide_height;
+
+        msg!("max peptide height lag: {}", internal.max_peptide_height_lag);
+        msg!("min peptide height: {}", internal.min_peptide_height);
+        msg!("height limits successfully updated");
+        Ok(())
+    }
+
+    /// configures a source chain proofs are accepted from, or updates its config. `client_type`
+    /// overrides the one in the internal account for the chain's events
+    pub fn set_chain_config(
+        ctx: Context<UpdateChainConfig>,
+        chain_id: u32,
+        enabled: bool,
+        label: String,
+        client_type: Option<String>,
+    ) -> Result<()> {
+        require!(label.len() <= MAX_CHAIN_LABEL_LEN, ErrorCode::ChainLabelTooLong);
+        if let Some(client_type) = &client_type {
+            require!(client_type.len() <= MAX_CLIENT_TYPE_LEN, ErrorCode::ClientTypeTooLong);
+        }
+
+        let chain_config = &mut ctx.accounts.chain_config;
+        chain_config.chain_id = chain_id;
+        chain_config.enabled = enabled;
+        chain_config.label = label;
+        chain_config.client_type = client_type;
+        ctx.accounts.internal.chain_configs_enforced = true;
+
+        emit!(ChainConfigUpdated {
+            chain_id,
+            enabled,
+            label: chain_config.label.clone(),
+            client_type: chain_config.client_type.clone(),
+        });
+
+        msg!("chain {} ({}) enabled: {}", chain_id, chain_config.label, enabled);
+        msg!("chain config successfully updated");
+        Ok(())
+    }
+
+    /// removes the config of a source chain, so its proofs are rejected again
+    pub fn remove_chain_config(_ctx: Context<RemoveChainConfig>, chain_id: u32) -> Result<()> {
+        emit!(ChainConfigRemoved { chain_id });
+        msg!("chain config successfully removed for chain {}", chain_id);
+        Ok(())
+    }
+
+    /// stops validating proofs until unpause is called, e.g. if peptide or a signer key is
+    /// compromised. Loading them stops too, except for callers of older versions that don't pass
+    /// the internal account. Users can still close their accounts
+    pub fn pause(ctx: Context<UpdatePause>) -> Result<()> {
+        ctx.accounts.internal.paused = true;
+
+        emit!(PauseUpdated { paused: true });
+        msg!("prover successfully paused");
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<UpdatePause>) -> Result<()> {
+        ctx.accounts.internal.paused = false;
+
+        emit!(PauseUpdated { paused: false });
+        msg!("prover successfully unpaused");
+        Ok(())
+    }
+
+    /// revokes a state root, or every state root in a peptide height range. Proofs against them
+    /// are rejected from then on, even if they are properly signed
+    pub fn add_revocation(ctx: Context<UpdateRevocations>, revocation: Revocation) -> Result<()> {
+        require!(revocation.is_valid(), ErrorCode::InvalidRevocation);
+
+        let revocations = &mut ctx.accounts.revocations.revocations;
+        require!(!revocations.contains(&revocation), ErrorCode::DuplicateRevocation);
+        require!(revocations.len() < MAX_REVOCATIONS, ErrorCode::TooManyRevocations);
+        revocations.push(revocation);
+        ctx.accounts.internal.revocation_count = revocations.len() as u8;
+
+        emit!(RevocationAdded { revocation });
+        msg!("revoked {}", revocation);
+        Ok(())
+    }
+
+    /// lifts a revocation added with add_revocation
+    pub fn remove_revocation(ctx: Context<UpdateRevocations>, revocation: Revocation) -> Result<()> {
+        let revocations = &mut ctx.accounts.revocations.revocations;
+        let index = revocations
+            .iter()
+            .position(|revoked| *revoked == revocation)
+            .ok_or(ErrorCode::RevocationNotFound)?;
+        revocations.remove(index);
+        ctx.accounts.internal.revocation_count = revocations.len() as u8;
+
+        emit!(RevocationRemoved { revocation });
+        msg!("no longer revoked: {}", revocation);
+        Ok(())
+    }
+
+    /// first step of the authority transfer. The new authority has to accept the role before it
+    /// takes effect. Proposing again replaces any pending proposal
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        let internal = &mut ctx.accounts.internal;
+        internal.pending_authority = Some(new_authority);
+
+        emit!(AuthorityProposed {
+            authority: internal.authority,
+            pending_authority: new_authority,
+        });
+
+        msg!("pending_authority: {}", new_authority);
+        msg!("authority successfully proposed");
+        Ok(())
+    }
+
+    /// second step of the authority transfer, signed by the proposed authority
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let internal = &mut ctx.accounts.internal;
+        let old_authority = internal.authority;
+
+        internal.authority = ctx.accounts.pending_authority.key();
+        internal.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            old_authority,
+            new_authority: internal.authority,
+        });
+
+        msg!("authority: {}", internal.authority);
+        msg!("authority successfully transferred");
+        Ok(())
+    }
+
+    pub fn create_accounts(_ctx: Context<CreateAccounts>) -> Result<()> {
+        msg!("accounts successfully created");
+        Ok(())
+    }
+
+    /// same as create_accounts but sized for proofs of `capacity` bytes, so they don't have to
+    /// grow while loading. Accounts above 10 KiB are created at that size and grown with
+    /// grow_accounts
+    pub fn create_accounts_with_capacity(_ctx: Context<CreateAccountsWithCapacity>, capacity: u32) -> Result<()> {
+        require!(capacity as usize <= MAX_PROOF_LEN, ErrorCode::InvalidProofLength);
+        msg!("accounts successfully created with capacity {}", capacity);
+        Ok(())
+    }
+
+    /// grows the cache and result accounts towards the size needed for proofs of `capacity`
+    /// bytes. An account can only grow 10 KiB per instruction, so larger capacities need this to
+    /// be called more than once
+    pub fn grow_accounts(ctx: Context<GrowAccounts>, capacity: u32) -> Result<()> {
+        let accounts = &ctx.accounts;
+        grow_accounts_for(
+            accounts.cache_account.to_account_info(),
+            accounts.result_account.to_account_info(),
+            capacity,
+            &accounts.authority,
+            &accounts.system_program,
+        )
+    }
+
+    pub fn close_accounts(ctx: Context<CloseAccounts>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        close_account(&accounts.cache_account, &accounts.authority)?;
+        close_account(&accounts.result_account, &accounts.authority)?;
+        msg!("accounts successfully closed");
+        Ok(())
+    }
+
+    pub fn clear_proof_cache(ctx: Context<ClearProofCache>) -> Result<()> {
+        msg!("proof cache successfully cleared");
+        ProofCacheAccount::clear_account_data(&mut ctx.accounts.cache_account.try_borrow_mut_data()?)
+    }
+
+    /// same as create_accounts but for the given slot. Each slot has its own cache and result
+    /// accounts so a single authority can process several proofs at the same time. Slot 0 is the
+    /// one used by the instructions without a slot
+    pub fn create_accounts_in_slot(_ctx: Context<CreateAccountsInSlot>, slot: u16) -> Result<()> {
+        msg!("accounts successfully created in slot {}", slot);
+        Ok(())
+    }
+
+    pub fn close_accounts_in_slot(ctx: Context<CloseAccountsInSlot>, slot: u16) -> Result<()> {
+        let accounts = &ctx.accounts;
+        close_account(&accounts.cache_account, &accounts.authority)?;
+        close_account(&accounts.result_account, &accounts.authority)?;
+        msg!("accounts successfully closed in slot {}", slot);
+        Ok(())
+    }
+
+    pub fn clear_proof_cache_in_slot(ctx: Context<ClearProofCacheInSlot>, slot: u16) -> Result<()> {
+        msg!("proof cache successfully cleared in slot {}", slot);
+        ProofCacheAccount::clear_account_data(&mut ctx.accounts.cache_account.try_borrow_mut_data()?)
+    }
+
+    // the slot is only used to derive the accounts
+    #[allow(unused_variables)]
+    pub fn grow_accounts_in_slot(ctx: Context<GrowAccountsInSlot>, slot: u16, capacity: u32) -> Result<()> {
+        let accounts = &ctx.accounts;
+        grow_accounts_for(
+            accounts.cache_account.to_account_info(),
+            accounts.result_account.to_account_info(),
+            capacity,
+            &accounts.authority,
+            &accounts.system_program,
+        )
+    }
+
+    // the slot is only used to derive the accounts
+    #[allow(unused_variables)]
+    pub fn set_expected_proof_hash_in_slot(
+        ctx: Context<SetExpectedProofHashInSlot>,
+        slot: u16,
+        expected_proof_hash: [u8; 32],
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        set_expected_hash(
+            &accounts.cache_account,
+            expected_proof_hash,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    // the slot is only used to derive the accounts
+    #[allow(unused_variables)]
+    pub fn load_proof_in_slot(ctx: Context<LoadProofInSlot>, slot: u16, proof_chunk: Vec<u8>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        append_chunk(
+            &accounts.cache_account,
+            &proof_chunk,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    // the slot is only used to derive the accounts
+    #[allow(unused_variables)]
+    pub fn load_proof_at_in_slot(
+        ctx: Context<LoadProofInSlot>,
+        slot: u16,
+        offset: u32,
+        total_len: u32,
+        proof_chunk: Vec<u8>,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        load_chunk_at(
+            &accounts.cache_account,
+            offset,
+            total_len,
+            &proof_chunk,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    // the slot is only used to derive the accounts
+    #[allow(unused_variables)]
+    pub fn validate_event_in_slot(ctx: Context<ValidateEventInSlot>, slot: u16) -> Result<ValidationReturn> {
+        let accounts = ctx.accounts;
+        let (revoked, chain) = accounts.config.load()?;
+        let result =
+            accounts
+                .config
+                .internal
+                .validate_cached_proof(&accounts.cache_account, &revoked, chain.as_ref())?;
+
+        msg!("{}", result);
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    pub fn resize_cache(ctx: Context<ResizeCache>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let cache = accounts.cache_account.to_account_info();
+        let space = CacheLayout::read(&cache.try_borrow_data()?)?
+            .space()
+            .max(DISCRIMINATOR_SIZE + ProofCacheAccount::INIT_SPACE);
+
+        if space > cache.data_len() {
+            grow_account(
+                cache.clone(),
+                space,
+                &accounts.authority,
+                Some(&accounts.system_program),
+            )?;
+        } else {
+            // refund the rent that is no longer needed
+            cache.realloc(space, false)?;
+            let refund = cache.lamports().saturating_sub(Rent::get()?.minimum_balance(space));
+            **cache.try_borrow_mut_lamports()? -= refund;
+            **accounts.authority.try_borrow_mut_lamports()? += refund;
+        }
+
+        msg!("cache account successfully resized to {} bytes", cache.data_len());
+        Ok(())
+    }
+
+    /// appends a chunk to the proof in the cache account, which grows as needed at the
+    /// authority's expense. It grows to fit proofs of the new length loaded either way
+    pub fn load_proof(ctx: Context<LoadProof>, proof_chunk: Vec<u8>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        append_chunk(
+            &accounts.cache_account,
+            &proof_chunk,
+            &accounts.authority,
+            accounts.system_program.as_ref(),
+        )
+    }
+
+    /// loads a chunk of a proof of `total_len` bytes at the given offset. Unlike load_proof, chunks
+    /// can be sent in any order, in parallel and retried any number of times. The proof can't be
+    /// validated until all its bytes are loaded
+    pub fn load_proof_at(ctx: Context<LoadProof>, offset: u32, total_len: u32, proof_chunk: Vec<u8>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        load_chunk_at(
+            &accounts.cache_account,
+            offset,
+            total_len,
+            &proof_chunk,
+            &accounts.authority,
+            accounts.system_program.as_ref(),
+        )
+    }
+
+    /// makes the validate instructions fail unless the loaded proof hashes (keccak256) to the given
+    /// hash, to make sure the cache holds the intended proof when several clients share the same
+    /// key. It must be sent before the first chunk is loaded, and it's reset when the cache is
+    /// cleared
+    pub fn set_expected_proof_hash(ctx: Context<SetExpectedProofHash>, expected_proof_hash: [u8; 32]) -> Result<()> {
+        let accounts = &ctx.accounts;
+        set_expected_hash(
+            &accounts.cache_account,
+            expected_proof_hash,
+            &accounts.authority,
+            accounts.system_program.as_ref(),
+        )
+    }
+
+    /// validates the proof in the cache account and stores the result in the result account. The
+    /// result is also set as the return data, which CPI callers can read with
+    /// `get_validation_return`
+    pub fn validate_event(ctx: Context<ValidateEvent>) -> Result<ValidationReturn> {
+        // the internal account is set by the owner/deployer during initialize()
+        let accounts = ctx.accounts;
+        let revoked = RevocationsAccount::load_optional(accounts.revocations.as_deref(), &accounts.internal)?;
+        let chain = ChainConfigAccount::load_optional(accounts.chain_config.as_deref())?;
+        let result = accounts
+            .internal
+            .validate_cached_proof(&accounts.cache_account, &revoked, chain.as_ref())?;
+        save_latest_peptide_height(&accounts.internal)?;
+
+        msg!("{}", result);
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            accounts.system_program.as_ref(),
+        )
+    }
+
+    /// same as validate_event but the state root signatures are checked by secp256k1 program
+    /// instructions in the same transaction, over `peptide_message`, instead of recovered here.
+    /// The attestations in the proof are ignored
+    pub fn validate_event_precompiled(ctx: Context<ValidateEventPrecompiled>) -> Result<ValidationReturn> {
+        let accounts = ctx.accounts;
+        let verified = secp256k1_precompile::load_verified_signatures(&accounts.instructions)?;
+        let (revoked, chain) = accounts.config.load()?;
+        let result = with_cached_proof(&accounts.cache_account, |proof| {
+            accounts
+                .config
+                .internal
+                .validate_proof_with_verified_signatures(proof, &revoked, chain.as_ref(), &verified)
+        })?;
+
+        msg!("{}", result);
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    /// checks the signatures over the state root at the given peptide height once and stores it,
+    /// so proofs at that height can be validated with validate_event_with_checkpoint. Each
+    /// signature is a (signature, recovery id) pair like the attestations in the proofs
+    pub fn submit_state_root(
+        ctx: Context<SubmitStateRoot>,
+        app_hash: [u8; 32],
+        peptide_height: u64,
+        signatures: Vec<[u8; 65]>,
+    ) -> Result<()> {
+        let revoked = RevocationsAccount::load(&ctx.accounts.revocations)?;
+        let internal = &mut ctx.accounts.internal;
+        if let Some(err) =
+            validate_event::verify_state_root(&app_hash, peptide_height, &signatures, &internal.policy(&revoked))
+        {
+            msg!("{}", err);
+            // failures always have an error code
+            return Err(err.error_code().unwrap().into());
+        }
+
+        internal.latest_peptide_height = internal.latest_peptide_height.max(peptide_height);
+
+        let state_root = &mut ctx.accounts.state_root;
+        state_root.app_hash = app_hash;
+        state_root.peptide_height = peptide_height;
+        state_root.submitter = ctx.accounts.payer.key();
+
+        emit!(StateRootSubmitted {
+            app_hash,
+            peptide_height,
+        });
+
+        msg!(
+            "state root 0x{} submitted at peptide height {}",
+            hex::encode(app_hash),
+            peptide_height
+        );
+        Ok(())
+    }
+
+    /// closes a state root submitted with submit_state_root and refunds its rent to the submitter.
+    /// The submitter can close it at any time. The authority can only close it once proofs can't
+    /// be validated against it anymore, because it's below the minimum peptide height or revoked
+    pub fn close_state_root(ctx: Context<CloseStateRoot>, peptide_height: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let state_root = &accounts.state_root;
+
+        if accounts.authority.key() != state_root.submitter {
+            require_keys_eq!(
+                accounts.authority.key(),
+                accounts.internal.authority,
+                ErrorCode::Unauthorized
+            );
+            let revoked = RevocationsAccount::load(&accounts.revocations)?;
+            require!(
+                peptide_height < accounts.internal.min_accepted_height()
+                    || revocation::is_revoked(&revoked, &state_root.app_hash, peptide_height),
+                ErrorCode::StateRootInUse
+            );
+        }
+
+        emit!(StateRootClosed {
+            app_hash: state_root.app_hash,
+            peptide_height,
+        });
+
+        msg!("state root at peptide height {} closed", peptide_height);
+        Ok(())
+    }
+
+    /// same as validate_event but against a state root submitted with submit_state_root at the
+    /// peptide height of the proof, so no signature is checked. Checkpoint proofs (see
+    /// `PROOF_CHECKPOINT`) leave the state root and its signature out altogether
+    pub fn validate_event_with_checkpoint(ctx: Context<ValidateEventWithCheckpoint>) -> Result<ValidationReturn> {
+        let accounts = ctx.accounts;
+        let (revoked, chain) = accounts.config.load()?;
+        let result = with_cached_proof(&accounts.cache_account, |proof| {
+            accounts.config.internal.validate_proof_with_state_root(
+                proof,
+                &revoked,
+                chain.as_ref(),
+                &accounts.state_root,
+            )
+        })?;
+
+        msg!("{}", result);
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+
+    /// validates a proof passed in the instruction data, for proofs small enough to fit in a
+    /// single transaction. There is no cache nor result account involved: the result is set as
+    /// the return data and emitted as an event
+    pub fn validate_event_inline(ctx: Context<ValidationConfig>, proof: Vec<u8>) -> Result<ValidationReturn> {
+        let (revoked, chain) = ctx.accounts.load()?;
+        let result = ctx.accounts.internal.validate_proof(&proof, &revoked, chain.as_ref());
+
+        msg!("{}", result);
+
+        let result = ValidationReturn::from(&ValidationResultAccount::from(result));
+        emit!(EventValidated { result: result.clone() });
+
+        Ok(result)
+    }
+
+    /// same as validate_event but an invalid proof fails the whole transaction with the error
+    /// code of the failure, so callers can't accidentally accept a bad event by not checking the
+    /// result account
+    pub fn validate_event_strict(ctx: Context<ValidateEvent>) -> Result<ValidationReturn> {
+        let accounts = ctx.accounts;
+        let revoked = RevocationsAccount::load_optional(accounts.revocations.as_deref(), &accounts.internal)?;
+        let chain = ChainConfigAccount::load_optional(accounts.chain_config.as_deref())?;
+        let result = accounts
+            .internal
+            .validate_cached_proof(&accounts.cache_account, &revoked, chain.as_ref())?;
+        save_latest_peptide_height(&accounts.internal)?;
+
+        msg!("{}", result);
+
+        if let Some(code) = result.error_code() {
+            return Err(code.into());
+        }
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            accounts.system_program.as_ref(),
+        )
+    }
+
+    /// same as validate_event but a given event can only be successfully validated once: a second
+    /// attempt fails with EventAlreadyConsumed. The event location must match the one in the proof
+    /// since it is used to derive the account that marks the event as consumed
+    pub fn validate_event_once(
+        ctx: Context<ValidateEventOnce>,
+        chain_id: u32,
+        block_number: u64,
+        tx_index: u32,
+        log_index: u32,
+    ) -> Result<ValidationReturn> {
+        let bump = ctx.bumps.consumed_event;
+        let accounts = ctx.accounts;
+        let (revoked, chain) = accounts.config.load()?;
+        let (result, location) = with_cached_proof(&accounts.cache_account, |proof| {
+            accounts
+                .config
+                .internal
+                .validate_with_location(proof, &revoked, chain.as_ref())
+        })?;
+
+        if let ValidateEventResult::Valid(..) = result {
+            require!(
+                location
+                    == Some(EventLocation {
+                        chain_id,
+                        block_number,
+                        tx_index,
+                        log_index,
+                    }),
+                ErrorCode::EventLocationMismatch
+            );
+
+            let consumed_event = accounts.consumed_event.to_account_info();
+            if consumed_event.owner == &crate::ID {
+                // older versions created it before checking the proof, so it may not be marked
+                let consumed = ConsumedEventAccount::try_deserialize(&mut &consumed_event.try_borrow_data()?[..])?;
+                require!(!consumed.is_consumed, ErrorCode::EventAlreadyConsumed);
+            } else {
+                create_pda(
+                    &consumed_event,
+                    DISCRIMINATOR_SIZE + ConsumedEventAccount::INIT_SPACE,
+                    &accounts.authority,
+                    &accounts.system_program,
+                    &[
+                        b"consumed",
+                        &chain_id.to_be_bytes(),
+                        &block_number.to_be_bytes(),
+                        &tx_index.to_be_bytes(),
+                        &log_index.to_be_bytes(),
+                        &[bump],
+                    ],
+                )?;
+            }
+
+            let consumed = ConsumedEventAccount {
+                is_consumed: true,
+                consumed_by: accounts.authority.key(),
+            };
+            consumed.try_serialize(&mut &mut consumed_event.try_borrow_mut_data()?[..])?;
+        }
+
+        msg!("{}", result);
+
+        store_result(
+            result,
+            &accounts.cache_account,
+            &accounts.result_account,
+            &accounts.authority,
+            Some(&accounts.system_program),
+        )
+    }
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("The signer is not the program authority.")]
+    Unauthorized,
+
+    #[msg("The client type is too long.")]
+    ClientTypeTooLong,
+
+    #[msg("The signer set must not be empty nor exceed the maximum number of signers.")]
+    InvalidSignerSet,
+
+    #[msg("The threshold must be between one and the number of signers.")]
+    InvalidThreshold,
+
+    #[msg("The signer set contains duplicate signers.")]
+    DuplicateSigner,
+
+    #[msg("The signer is not the pending authority.")]
+    NotPendingAuthority,
+
+    #[msg("The event location does not match the one in the proof.")]
+    EventLocationMismatch,
+
+    // ProofTooShort to NotEnoughSigners, StalePeptideHeight, StateRootHeightMismatch,
+    // RevokedStateRoot and UnsupportedChain are validation failures. The validate instructions
+    // succeed and store them in `ValidationResultAccount.error_code`, while
+    // validate_event_strict and submit_state_root return them. All the other codes, including
+    // EventAlreadyConsumed, are only returned by instructions
+    #[msg("The proof is too short.")]
+    ProofTooShort,
+
+    #[msg("The proof version is not supported.")]
+    UnsupportedProofVersion,
+
+    #[msg("The event in the proof is malformed.")]
+    InvalidEvent,
+
+    #[msg("The membership proof is malformed.")]
+    InvalidMembershipProof,
+
+    #[msg("The membership proof does not lead to the signed state root.")]
+    InvalidStateRoot,
+
+    #[msg("A signature over the state root is malformed or can't be recovered.")]
+    InvalidSignature,
+
+    #[msg("The state root was signed by an unknown signer.")]
+    UnknownSigner,
+
+    #[msg("The state root was signed more than once by the same signer.")]
+    DuplicateAttestation,
+
+    #[msg("The state root was not signed by enough signers.")]
+    NotEnoughSigners,
+
+    #[msg("The event was already consumed.")]
+    EventAlreadyConsumed,
+
+    #[msg("No return data was set.")]
+    MissingReturnData,
+
+    #[msg("The return data was not set by the prover.")]
+    WrongReturnDataProgram,
+
+    #[msg("The proof length must be between one and the maximum proof length.")]
+    InvalidProofLength,
+
+    #[msg("The proof length does not match the one of the proof being loaded.")]
+    ProofLengthMismatch,
+
+    #[msg("The proof chunk does not fit in the proof.")]
+    ChunkOutOfBounds,
+
+    #[msg("The proof cache was loaded with a different instruction. Clear it first.")]
+    MixedProofLoading,
+
+    #[msg("Not all the proof chunks were loaded yet.")]
+    ProofIncomplete,
+
+    #[msg("The loaded proof does not match the expected proof hash.")]
+    ProofHashMismatch,
+
+    #[msg("The state root is older than the configured peptide height limits allow.")]
+    StalePeptideHeight,
+
+    #[msg("The proof is not at the same peptide height as the submitted state root.")]
+    StateRootHeightMismatch,
+
+    #[msg("The state root was revoked by the authority.")]
+    RevokedStateRoot,
+
+    #[msg("The revoked height range ends before it starts.")]
+    InvalidRevocation,
+
+    #[msg("The state root is already revoked.")]
+    DuplicateRevocation,
+
+    #[msg("There are too many revocations. Remove some first.")]
+    TooManyRevocations,
+
+    #[msg("The revocation was not found.")]
+    RevocationNotFound,
+
+    #[msg("The prover is paused by the authority.")]
+    ProgramPaused,
+
+    #[msg("The source chain of the proof is not configured or is disabled.")]
+    UnsupportedChain,
+
+    #[msg("The chain label is too long.")]
+    ChainLabelTooLong,
+
+    #[msg("The signer address can't be changed while a signer set is configured. Use update_signers instead.")]
+    SignerSetConfigured,
+
+    #[msg("The account can't grow more than 10 KiB per instruction. Grow it first with grow_accounts.")]
+    AccountTooSmall,
+
+    #[msg("The expected proof hash must be set before loading the proof. Clear the cache first.")]
+    ProofAlreadyLoading,
+
+    #[msg("The revocations account must be passed while state roots are revoked.")]
+    RevocationsAccountRequired,
+
+    #[msg("Proofs can still be validated against the state root. Only its submitter can close it.")]
+    StateRootInUse,
+}
@@ -0,0 +1,290 @@
+//! In-memory stand-in for the peptide store. It holds a set of events, computes the IAVL-style
+//! state root over all of them and hands out `ProofBuilder`s with the membership path of any of
+//! them, so proofs can be generated for many synthetic events without a running peptide.
+
+use crate::instructions::parse_event::EthAddress;
+use crate::instructions::proof::EventLocation;
+use crate::testing::proof_builder::{encode_varint, inner_hash, PathStep, ProofBuilder, SecretKey};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone)]
+struct Leaf {
+    location: EventLocation,
+    emitting_contract: EthAddress,
+    topics: Vec<[u8; 32]>,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    hash: [u8; 32],
+    height: i64,
+    size: i64,
+}
+
+pub struct MockStateTree {
+    client_type: String,
+    peptide_chain_id: u64,
+    peptide_height: u64,
+
+    /// sorted by key, like the IAVL store
+    leaves: BTreeMap<String, Leaf>,
+
+    /// nodes by the [start, end) range of leaves they cover. Cleared on every insert
+    nodes: HashMap<(usize, usize), Node>,
+}
+
+impl MockStateTree {
+    /// All the nodes in the tree are created at `peptide_height`, which is also the height the
+    /// root is signed at
+    pub fn new(client_type: &str, peptide_chain_id: u64, peptide_height: u64) -> Self {
+        MockStateTree {
+            client_type: client_type.to_string(),
+            peptide_chain_id,
+            peptide_height,
+            leaves: BTreeMap::new(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Stores the event under its key, replacing any previous event at the same location
+    pub fn insert(
+        &mut self,
+        location: EventLocation,
+        emitting_contract: EthAddress,
+        topics: Vec<[u8; 32]>,
+        data: Vec<u8>,
+    ) {
+        self.nodes.clear();
+        self.leaves.insert(
+            location.key(&self.client_type),
+            Leaf {
+                location,
+                emitting_contract,
+                topics,
+                data,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn root(&mut self) -> Option<[u8; 32]> {
+        if self.is_empty() {
+            return None;
+        }
+        self.compute_nodes();
+        Some(self.nodes[&(0, self.len())].hash)
+    }
+
+    /// Builder with everything but the signers filled in for the event at the given location
+    pub fn proof_builder(&mut self, location: &EventLocation) -> Option<ProofBuilder> {
+        let index = self
+            .leaves
+            .keys()
+            .position(|key| *key == location.key(&self.client_type))?;
+        self.compute_nodes();
+
+        // walk from the root down to the leaf, collecting the siblings along the way
+        let mut path = Vec::new();
+        let (mut start, mut end) = (0, self.len());
+        while end - start > 1 {
+            let node = self.nodes[&(start, end)];
+            let mid = split(start, end);
+            let mut prefix = [
+                encode_varint(node.height),
+                encode_varint(node.size),
+                encode_varint(self.version()),
+            ]
+            .concat();
+
+            if index < mid {
+                prefix.push(0x20);
+                path.push(PathStep {
+                    prefix,
+                    suffix: [&[0x20][..], &self.nodes[&(mid, end)].hash].concat(),
+                });
+                end = mid;
+            } else {
+                prefix.push(0x20);
+                prefix.extend(self.nodes[&(start, mid)].hash);
+                prefix.push(0x20);
+                path.push(PathStep { prefix, suffix: vec![] });
+                start = mid;
+            }
+        }
+        path.reverse();
+
+        let leaf = self.leaves.values().nth(index)?;
+        Some(self.leaf_builder(leaf).path(path))
+    }
+
+    /// Signed proof for the event at the given location
+    pub fn proof(&mut self, location: &EventLocation, signers: &[SecretKey]) -> Option<Vec<u8>> {
+        let builder = signers
+            .iter()
+            .fold(self.proof_builder(location)?, |builder, key| builder.signer(*key));
+        builder.build().ok()
+    }
+
+    fn version(&self) -> i64 {
+        self.peptide_height as i64
+    }
+
+    fn leaf_builder(&self, leaf: &Leaf) -> ProofBuilder {
+        ProofBuilder::new(&self.client_type, self.peptide_chain_id)
+            .event(leaf.emitting_contract, leaf.topics.clone(), leaf.data.clone())
+            .location(leaf.location)
+            .peptide_height(self.peptide_height)
+            .leaf_prefix([encode_varint(0), encode_varint(1), encode_varint(self.version())].concat())
+    }
+
+    fn compute_nodes(&mut self) {
+        if self.nodes.is_empty() && !self.is_empty() {
+            let leaves: Vec<Leaf> = self.leaves.values().cloned().collect();
+            self.compute_node(&leaves, 0, leaves.len());
+        }
+    }
+
+    fn compute_node(&mut self, leaves: &[Leaf], start: usize, end: usize) -> Node {
+        let node = if end - start == 1 {
+            Node {
+                // with no path, the builder's state root is just the leaf hash
+                hash: self.leaf_builder(&leaves[start]).computed_state_root(),
+                height: 0,
+                size: 1,
+            }
+        } else {
+            let mid = split(start, end);
+            let left = self.compute_node(leaves, start, mid);
+            let right = self.compute_node(leaves, mid, end);
+            let height = 1 + left.height.max(right.height);
+            let size = left.size + right.size;
+            let step = PathStep {
+                prefix: [
+                    encode_varint(height),
+                    encode_varint(size),
+                    encode_varint(self.version()),
+                    vec![0x20],
+                ]
+                .concat(),
+                suffix: [&[0x20][..], &right.hash].concat(),
+            };
+
+            Node {
+                hash: inner_hash(&step, &left.hash),
+                height,
+                size,
+            }
+        };
+
+        self.nodes.insert((start, end), node);
+        node
+    }
+}
+
+fn split(start: usize, end: usize) -> usize {
+    start + (end - start).div_ceil(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::validate_event::{handler, StateRootPolicy, ValidateEventResult};
+    use crate::testing::proof_builder::eth_address;
+
+    fn key(seed: u8) -> SecretKey {
+        SecretKey::parse(&[seed; 32]).unwrap()
+    }
+
+    fn policy(signers: &[[u8; 20]], threshold: usize) -> StateRootPolicy<'_> {
+        StateRootPolicy {
+            signers,
+            threshold,
+            peptide_chain_id: 901,
+            ..Default::default()
+        }
+    }
+
+    fn location(i: u32) -> EventLocation {
+        EventLocation {
+            chain_id: 10 + i % 3,
+            block_number: 1000 + u64::from(i / 7),
+            tx_index: i % 7,
+            log_index: i % 2,
+        }
+    }
+
+    fn tree(num_events: u32) -> MockStateTree {
+        let mut tree = MockStateTree::new("proof_api", 901, 3_130_134);
+        for i in 0..num_events {
+            tree.insert(
+                location(i),
+                EthAddress::from_hex(&format!("{:x}", i + 1)),
+                vec![[i as u8; 32]; (i % 5) as usize],
+                i.to_be_bytes().to_vec(),
+            );
+        }
+        tree
+    }
+
+    #[test]
+    fn test_every_event_is_provable() {
+        let mut tree = tree(1000);
+        let signers = [*eth_address(&key(1)).as_bytes()];
+
+        for i in 0..1000 {
+            let proof = tree.proof(&location(i), &[key(1)]).unwrap();
+            let result = handler(&proof, "proof_api", &policy(&signers, 1));
+            let ValidateEventResult::Valid(chain_id, event) = result else {
+                panic!("event {} is not valid: {}", i, result);
+            };
+
+            assert_eq!(location(i).chain_id, chain_id);
+            assert_eq!(i.to_be_bytes().to_vec(), event.unindexed_data);
+        }
+    }
+
+    #[test]
+    fn test_small_trees() {
+        let signers = [*eth_address(&key(1)).as_bytes(), *eth_address(&key(2)).as_bytes()];
+
+        for num_events in 1..8 {
+            let mut tree = tree(num_events);
+            assert_eq!(num_events as usize, tree.len());
+
+            for i in 0..num_events {
+                let proof = tree.proof(&location(i), &[key(1), key(2)]).unwrap();
+                let result = handler(&proof, "proof_api", &policy(&signers, 2));
+                assert!(matches!(result, ValidateEventResult::Valid(_, _)), "{}", result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_root_changes_on_insert() {
+        let mut tree = tree(10);
+        let root = tree.root().unwrap();
+        let stale_proof = tree.proof(&location(0), &[key(1)]).unwrap();
+
+        tree.insert(location(10), EthAddress::default(), vec![], vec![]);
+        assert_ne!(root, tree.root().unwrap());
+
+        let fresh_proof = tree.proof(&location(0), &[key(1)]).unwrap();
+        assert_ne!(stale_proof, fresh_proof);
+        assert_eq!(tree.root().unwrap(), fresh_proof[0..32]);
+    }
+
+    #[test]
+    fn test_missing_event() {
+        let mut tree = tree(10);
+        assert!(tree.proof_builder(&location(10)).is_none());
+        assert!(MockStateTree::new("proof_api", 901, 1).root().is_none());
+    }
+}
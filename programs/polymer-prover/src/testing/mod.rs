@@ -0,0 +1,4 @@
+//! Off-chain helpers to generate proofs in tests, available behind the `proof-builder` feature.
+
+pub mod mock_state_tree;
+pub mod proof_builder;
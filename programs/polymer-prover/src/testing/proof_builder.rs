@@ -0,0 +1,487 @@
+//! Off-chain encoder for the proofs accepted by `validate_event::handler`. It is meant for tests
+//! and tools that need to produce proofs without a running peptide and proof-api.
+
+use crate::instructions::parse_event::EthAddress;
+use crate::instructions::proof::{EventLocation, ATTESTATION_SIZE, PROOF_CHECKPOINT, PROOF_MAGIC, PROOF_V2};
+use crate::instructions::validate_event::peptide_message_hash;
+use anchor_lang::solana_program::keccak;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::fmt;
+
+pub use libsecp256k1::SecretKey;
+
+/// IAVL leaf prefix for a leaf at height 0, with size 1 and version 1
+pub const DEFAULT_LEAF_PREFIX: [u8; 3] = [0x00, 0x02, 0x02];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofBuilderError {
+    MissingSigner,
+    TooManyTopics(usize),
+    EventTooLarge(usize),
+    TooManyPathSteps(usize),
+    LeafPrefixTooLarge(usize),
+    PathStepTooLarge(usize),
+}
+
+impl fmt::Display for ProofBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofBuilderError::MissingSigner => write!(f, "at least one signer is needed"),
+            ProofBuilderError::TooManyTopics(n) => write!(f, "too many topics: {}", n),
+            ProofBuilderError::EventTooLarge(n) => write!(f, "event is too large: {} bytes", n),
+            ProofBuilderError::TooManyPathSteps(n) => write!(f, "too many path steps: {}", n),
+            ProofBuilderError::LeafPrefixTooLarge(n) => write!(f, "leaf prefix is too large: {} bytes", n),
+            ProofBuilderError::PathStepTooLarge(i) => write!(f, "path step {} is too large", i),
+        }
+    }
+}
+
+/// Inner node on the path from the leaf to the state root. Its hash is
+/// sha256(prefix || child hash || suffix)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PathStep {
+    pub prefix: Vec<u8>,
+    pub suffix: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct ProofBuilder {
+    client_type: String,
+    peptide_chain_id: u64,
+    peptide_height: u64,
+    location: EventLocation,
+    emitting_contract: EthAddress,
+    topics: Vec<[u8; 32]>,
+    data: Vec<u8>,
+    leaf_prefix: Vec<u8>,
+    path: Vec<PathStep>,
+    signers: Vec<SecretKey>,
+    state_root: Option<[u8; 32]>,
+    versioned: bool,
+}
+
+impl ProofBuilder {
+    pub fn new(client_type: &str, peptide_chain_id: u64) -> Self {
+        ProofBuilder {
+            client_type: client_type.to_string(),
+            peptide_chain_id,
+            peptide_height: 0,
+            location: EventLocation {
+                chain_id: 0,
+                block_number: 0,
+                tx_index: 0,
+                log_index: 0,
+            },
+            emitting_contract: EthAddress::default(),
+            topics: Vec::new(),
+            data: Vec::new(),
+            leaf_prefix: DEFAULT_LEAF_PREFIX.to_vec(),
+            path: Vec::new(),
+            signers: Vec::new(),
+            state_root: None,
+            versioned: false,
+        }
+    }
+
+    pub fn event(mut self, emitting_contract: EthAddress, topics: Vec<[u8; 32]>, data: Vec<u8>) -> Self {
+        self.emitting_contract = emitting_contract;
+        self.topics = topics;
+        self.data = data;
+        self
+    }
+
+    pub fn location(mut self, location: EventLocation) -> Self {
+        self.location = location;
+        self
+    }
+
+    pub fn peptide_height(mut self, peptide_height: u64) -> Self {
+        self.peptide_height = peptide_height;
+        self
+    }
+
+    /// IAVL leaf prefix, without the key length, which is appended when building the proof
+    pub fn leaf_prefix(mut self, leaf_prefix: Vec<u8>) -> Self {
+        self.leaf_prefix = leaf_prefix;
+        self
+    }
+
+    /// Path steps from the leaf up to the state root
+    pub fn path(mut self, path: Vec<PathStep>) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Adds a key that signs the state root. The first one goes into the proof header and the
+    /// rest are appended after the membership proof
+    pub fn signer(mut self, key: SecretKey) -> Self {
+        self.signers.push(key);
+        self
+    }
+
+    /// Signs this state root instead of the one computed from the membership proof. Useful to
+    /// build invalid proofs
+    pub fn state_root(mut self, state_root: [u8; 32]) -> Self {
+        self.state_root = Some(state_root);
+        self
+    }
+
+    /// Prepends the versioned header, which legacy v2 proofs do not have
+    pub fn versioned(mut self, versioned: bool) -> Self {
+        self.versioned = versioned;
+        self
+    }
+
+    /// Key under which peptide stores the event
+    pub fn key(&self) -> String {
+        self.location.key(&self.client_type)
+    }
+
+    /// emitting contract || topics || unindexed data
+    pub fn raw_event(&self) -> Vec<u8> {
+        let mut raw_event = self.emitting_contract.as_bytes().to_vec();
+        self.topics.iter().for_each(|topic| raw_event.extend(topic));
+        raw_event.extend(&self.data);
+        raw_event
+    }
+
+    /// Leaf prefix as it goes in the proof, with the key length appended
+    pub fn full_leaf_prefix(&self) -> Vec<u8> {
+        let mut prefix = self.leaf_prefix.clone();
+        prefix.extend(encode_uvarint(self.key().len() as u64));
+        prefix
+    }
+
+    /// Value stored under the event key
+    pub fn value(&self) -> [u8; 32] {
+        keccak::hash(&self.raw_event()).0
+    }
+
+    /// State root computed from the leaf and the path steps, unless one was explicitly set
+    pub fn computed_state_root(&self) -> [u8; 32] {
+        if let Some(state_root) = self.state_root {
+            return state_root;
+        }
+
+        let leaf = leaf_hash(&self.full_leaf_prefix(), self.key().as_bytes(), &self.value());
+        self.path.iter().fold(leaf, |child, step| inner_hash(step, &child))
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, ProofBuilderError> {
+        let first_signer = self.signers.first().ok_or(ProofBuilderError::MissingSigner)?;
+        let state_root = self.computed_state_root();
+
+        let mut proof = Vec::new();
+        if self.versioned {
+            proof.extend(PROOF_MAGIC);
+            proof.push(PROOF_V2);
+        }
+
+        proof.extend(state_root);
+        proof.extend(self.attest(first_signer, &state_root));
+        proof.extend(self.event_and_membership_proof(97)?);
+
+        for signer in self.signers.iter().skip(1) {
+            proof.extend(self.attest(signer, &state_root));
+        }
+
+        Ok(proof)
+    }
+
+    /// Builds a checkpoint proof, to be validated against the state root submitted with
+    /// `state_root_signatures`. The signers are not used
+    pub fn build_checkpoint(&self) -> Result<Vec<u8>, ProofBuilderError> {
+        let mut proof = PROOF_MAGIC.to_vec();
+        proof.push(PROOF_CHECKPOINT);
+        proof.extend(self.event_and_membership_proof(0)?);
+        Ok(proof)
+    }
+
+    /// Signatures of every signer over the state root, as submit_state_root takes them
+    pub fn state_root_signatures(&self) -> Vec<[u8; ATTESTATION_SIZE]> {
+        let state_root = self.computed_state_root();
+        self.signers
+            .iter()
+            .map(|signer| self.attest(signer, &state_root).try_into().unwrap())
+            .collect()
+    }
+
+    /// Everything after the signed state root, which takes `offset` bytes before it
+    fn event_and_membership_proof(&self, offset: usize) -> Result<Vec<u8>, ProofBuilderError> {
+        let num_topics =
+            u8::try_from(self.topics.len()).map_err(|_| ProofBuilderError::TooManyTopics(self.topics.len()))?;
+
+        let raw_event = self.raw_event();
+        let event_end = u16::try_from(offset + 31 + raw_event.len())
+            .map_err(|_| ProofBuilderError::EventTooLarge(raw_event.len()))?;
+
+        let mut proof = Vec::new();
+        proof.extend(self.location.chain_id.to_be_bytes());
+        proof.extend(self.peptide_height.to_be_bytes());
+        proof.extend(self.location.block_number.to_be_bytes());
+        proof.extend(self.location.tx_index.to_be_bytes());
+        proof.extend(self.location.log_index.to_be_bytes());
+        proof.push(num_topics);
+        proof.extend(event_end.to_be_bytes());
+        proof.extend(raw_event);
+        proof.extend(self.membership_proof()?);
+
+        Ok(proof)
+    }
+
+    fn membership_proof(&self) -> Result<Vec<u8>, ProofBuilderError> {
+        let leaf_prefix = self.full_leaf_prefix();
+        let number_of_paths =
+            u8::try_from(self.path.len()).map_err(|_| ProofBuilderError::TooManyPathSteps(self.path.len()))?;
+        let path_zero_start = u8::try_from(2 + leaf_prefix.len())
+            .map_err(|_| ProofBuilderError::LeafPrefixTooLarge(leaf_prefix.len()))?;
+
+        let mut proof = vec![number_of_paths, path_zero_start];
+        proof.extend(leaf_prefix);
+
+        for (i, step) in self.path.iter().enumerate() {
+            let suffix_start =
+                u8::try_from(2 + step.prefix.len()).map_err(|_| ProofBuilderError::PathStepTooLarge(i))?;
+            let suffix_end = suffix_start
+                .checked_add(u8::try_from(step.suffix.len()).map_err(|_| ProofBuilderError::PathStepTooLarge(i))?)
+                .ok_or(ProofBuilderError::PathStepTooLarge(i))?;
+
+            proof.push(suffix_start);
+            proof.push(suffix_end);
+            proof.extend(&step.prefix);
+            proof.extend(&step.suffix);
+        }
+
+        Ok(proof)
+    }
+
+    /// Signs the state root the way peptide does and returns the signature || recovery id pair
+    fn attest(&self, key: &SecretKey, state_root: &[u8; 32]) -> Vec<u8> {
+        let hash = peptide_message_hash(self.peptide_chain_id, state_root, &self.peptide_height.to_be_bytes());
+        let (signature, recovery_id) = libsecp256k1::sign(&libsecp256k1::Message::parse(&hash), key);
+
+        let mut attestation = signature.serialize().to_vec();
+        attestation.push(recovery_id.serialize() + 27);
+        attestation
+    }
+}
+
+/// Ethereum address of the given key, as recovered by the prover
+pub fn eth_address(key: &SecretKey) -> EthAddress {
+    let pubkey = libsecp256k1::PublicKey::from_secret_key(key);
+    let hash = Keccak256::digest(&pubkey.serialize()[1..]);
+    EthAddress::from_bytes(&hash[12..32])
+}
+
+/// sha256(prefix || key || len(value hash) || sha256(value))
+pub fn leaf_hash(prefix: &[u8], key: &[u8], value: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(prefix);
+    hasher.update(key);
+    hasher.update([32u8; 1]);
+    hasher.update(Sha256::digest(value));
+    hasher.finalize().into()
+}
+
+/// sha256(prefix || child || suffix)
+pub fn inner_hash(step: &PathStep, child: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(&step.prefix);
+    hasher.update(child);
+    hasher.update(&step.suffix);
+    hasher.finalize().into()
+}
+
+/// Protobuf style unsigned varint
+pub fn encode_uvarint(mut value: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+    out
+}
+
+/// Zigzag encoded signed varint, as used by IAVL for heights, sizes and versions
+pub fn encode_varint(value: i64) -> Vec<u8> {
+    encode_uvarint(((value << 1) ^ (value >> 63)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::proof::Proof;
+    use crate::instructions::validate_event::{
+        handler, handler_with_checkpoint, verify_state_root, StateRootPolicy, ValidateEventResult,
+    };
+
+    fn read_proof_file(file_path: &str) -> Vec<u8> {
+        let contents = std::fs::read_to_string(file_path).expect("could not read hex file");
+        hex::decode(&contents.trim().as_bytes()[2..]).expect("could not decode hex file")
+    }
+
+    fn key(seed: u8) -> SecretKey {
+        SecretKey::parse(&[seed; 32]).unwrap()
+    }
+
+    fn policy(signers: &[[u8; 20]], threshold: usize) -> StateRootPolicy<'_> {
+        StateRootPolicy {
+            signers,
+            threshold,
+            peptide_chain_id: 901,
+            ..Default::default()
+        }
+    }
+
+    fn builder() -> ProofBuilder {
+        ProofBuilder::new("proof_api", 901)
+            .event(
+                EthAddress::from_hex("0xabcd"),
+                vec![[1; 32], [2; 32]],
+                b"some data".to_vec(),
+            )
+            .location(EventLocation {
+                chain_id: 10,
+                block_number: 1234,
+                tx_index: 5,
+                log_index: 6,
+            })
+            .peptide_height(42)
+            .path(vec![
+                PathStep {
+                    prefix: [encode_varint(1), encode_varint(2), encode_varint(1), vec![0x20]].concat(),
+                    suffix: [vec![0x20], vec![7; 32]].concat(),
+                },
+                PathStep {
+                    prefix: [
+                        encode_varint(2),
+                        encode_varint(3),
+                        encode_varint(1),
+                        vec![0x20],
+                        vec![8; 32],
+                        vec![0x20],
+                    ]
+                    .concat(),
+                    suffix: vec![],
+                },
+            ])
+    }
+
+    #[test]
+    fn test_built_proof_is_valid() {
+        let proof = builder().signer(key(1)).build().unwrap();
+
+        let result = handler(&proof, "proof_api", &policy(&[*eth_address(&key(1)).as_bytes()], 1));
+        let ValidateEventResult::Valid(chain_id, event) = result else {
+            panic!("expected valid proof, got {}", result);
+        };
+
+        assert_eq!(10, chain_id);
+        assert_eq!(EthAddress::from_hex("0xabcd"), event.emitting_contract);
+        assert_eq!([[1; 32], [2; 32]].concat(), event.topics);
+        assert_eq!(b"some data".to_vec(), event.unindexed_data);
+    }
+
+    #[test]
+    fn test_built_proof_with_many_signers() {
+        let proof = builder().signer(key(1)).signer(key(2)).versioned(true).build().unwrap();
+        let signers = [*eth_address(&key(1)).as_bytes(), *eth_address(&key(2)).as_bytes()];
+
+        let parsed = Proof::parse(&proof).unwrap();
+        assert_eq!(2, parsed.attestations.len());
+
+        let result = handler(&proof, "proof_api", &policy(&signers, 2));
+        assert!(matches!(result, ValidateEventResult::Valid(10, _)));
+    }
+
+    #[test]
+    fn test_built_invalid_proofs() {
+        let signers = [*eth_address(&key(1)).as_bytes()];
+
+        assert_eq!(Err(ProofBuilderError::MissingSigner), builder().build());
+
+        let proof = builder().signer(key(2)).build().unwrap();
+        let result = handler(&proof, "proof_api", &policy(&signers, 1));
+        assert_eq!(
+            ValidateEventResult::RecoveredInvalidSignerAddress(eth_address(&key(2))),
+            result
+        );
+
+        let proof = builder().signer(key(1)).state_root([9; 32]).build().unwrap();
+        let result = handler(&proof, "proof_api", &policy(&signers, 1));
+        assert!(matches!(result, ValidateEventResult::InvalidStateRoot(_)));
+    }
+
+    #[test]
+    fn test_built_checkpoint_proof() {
+        let builder = builder().signer(key(1)).signer(key(2));
+        let signers = [*eth_address(&key(1)).as_bytes(), *eth_address(&key(2)).as_bytes()];
+        let state_root = builder.computed_state_root();
+
+        let signatures = builder.state_root_signatures();
+        assert_eq!(
+            None,
+            verify_state_root(&state_root, 42, &signatures, &policy(&signers, 2))
+        );
+
+        let proof = builder.build_checkpoint().unwrap();
+        let result = handler_with_checkpoint(&proof, "proof_api", &policy(&signers, 2), &state_root, 42);
+        assert!(matches!(result, ValidateEventResult::Valid(10, _)));
+
+        // the same event is found in the full proof
+        let full = builder.build().unwrap();
+        let full = Proof::parse(&full).unwrap();
+        let parsed = Proof::parse(&proof).unwrap();
+        assert_eq!(full.raw_event, parsed.raw_event);
+        assert_eq!(full.membership_proof, parsed.membership_proof);
+
+        // without the state root, the signatures can't be checked
+        let result = handler(&proof, "proof_api", &policy(&signers, 2));
+        assert_eq!(ValidateEventResult::UnsupportedProofVersion(PROOF_CHECKPOINT), result);
+    }
+
+    #[test]
+    fn test_rebuild_fixture() {
+        let fixture = read_proof_file("src/instructions/test-data/op-proof-v2.hex");
+        let parsed = Proof::parse(&fixture).unwrap();
+        let raw_event = parsed.raw_event;
+        let topics_end = 20 + 32 * parsed.num_topics;
+
+        let leaf_prefix = parsed.membership_proof.leaf_prefix;
+        let proof = ProofBuilder::new("proof_api", 901)
+            .event(
+                EthAddress::from_bytes(&raw_event[..20]),
+                raw_event[20..topics_end]
+                    .chunks_exact(32)
+                    .map(|topic| <[u8; 32]>::try_from(topic).unwrap())
+                    .collect(),
+                raw_event[topics_end..].to_vec(),
+            )
+            .location(parsed.location)
+            .peptide_height(parsed.peptide_height)
+            // drop the key length, the builder adds it back
+            .leaf_prefix(leaf_prefix[..leaf_prefix.len() - 1].to_vec())
+            .path(
+                parsed
+                    .membership_proof
+                    .path
+                    .iter()
+                    .map(|step| PathStep {
+                        prefix: step.prefix.to_vec(),
+                        suffix: step.suffix.to_vec(),
+                    })
+                    .collect(),
+            )
+            .signer(key(1));
+
+        assert_eq!(parsed.app_hash, proof.computed_state_root());
+
+        // everything but the signature must match the fixture
+        let built = proof.build().unwrap();
+        assert_eq!(fixture.len(), built.len());
+        assert_eq!(fixture[..32], built[..32]);
+        assert_eq!(fixture[97..], built[97..]);
+    }
+}
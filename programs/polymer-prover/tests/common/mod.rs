@@ -0,0 +1,272 @@
+//! Runs the compiled program with mollusk. The program has to be built first with `make build`
+//! (or `anchor build`), which leaves it in target/deploy. Set SBF_OUT_DIR to load it from
+//! somewhere else. The tests using it are ignored by default, `make test` runs them
+
+#![allow(dead_code)]
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use mollusk_svm::{
+    program::{create_program_account_loader_v3, keyed_account_for_system_program, loader_keys},
+    result::{InstructionResult, ProgramResult},
+    Mollusk,
+};
+use solana_sdk::{
+    account::Account, instruction::Instruction, native_token::LAMPORTS_PER_SOL, program_error::ProgramError,
+    pubkey::Pubkey, system_program,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+pub const CLIENT_TYPE: &str = "proof_api";
+pub const PEPTIDE_CHAIN_ID: u64 = 901;
+pub const SIGNER_ADDR: &str = "8D3921B96A3815F403Fb3a4c7fF525969d16f9E0";
+
+/// source chains of the op and arb fixtures
+pub const OP_CHAIN_ID: u32 = 11_155_420;
+pub const ARB_CHAIN_ID: u32 = 421_614;
+
+/// the default limit is too low to validate the larger proofs
+pub const COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+pub struct Harness {
+    pub mollusk: Mollusk,
+    pub accounts: HashMap<Pubkey, Account>,
+}
+
+impl Harness {
+    /// Loads the polymer prover program. Panics if it hasn't been built
+    pub fn new() -> Self {
+        let elf = load_program_elf("polymer_prover");
+
+        let mut mollusk = Mollusk::default();
+        mollusk.compute_budget.compute_unit_limit = COMPUTE_UNIT_LIMIT;
+        mollusk.add_program_with_elf_and_loader(&polymer_prover::ID, &elf, &loader_keys::LOADER_V3);
+
+        let (system_id, system_account) = keyed_account_for_system_program();
+        let accounts = HashMap::from([
+            (system_id, system_account),
+            (
+                polymer_prover::ID,
+                create_program_account_loader_v3(&polymer_prover::ID),
+            ),
+        ]);
+
+        Harness { mollusk, accounts }
+    }
+
+    /// Loads another program, e.g. one that calls the prover. Panics if it hasn't been built
+    pub fn add_program(&mut self, program_id: Pubkey, name: &str) {
+        let elf = load_program_elf(name);
+
+        self.mollusk
+            .add_program_with_elf_and_loader(&program_id, &elf, &loader_keys::LOADER_V3);
+        self.accounts
+            .insert(program_id, create_program_account_loader_v3(&program_id));
+    }
+
+    /// Adds a new system account with some lamports, as if it was a funded wallet
+    pub fn new_wallet(&mut self) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.accounts
+            .insert(wallet, Account::new(10 * LAMPORTS_PER_SOL, 0, &system_program::ID));
+        wallet
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Account {
+        self.accounts.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Processes the instruction against the current state of the accounts. They are only
+    /// updated if the instruction succeeds, like in a real transaction
+    pub fn process(&mut self, instruction: &Instruction) -> InstructionResult {
+        let mut keys: Vec<Pubkey> = Vec::new();
+        for meta in instruction.accounts.iter() {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let accounts: Vec<(Pubkey, Account)> = keys.iter().map(|key| (*key, self.account(key))).collect();
+
+        let result = self.mollusk.process_instruction(instruction, &accounts);
+        if !result.program_result.is_err() {
+            self.accounts.extend(result.resulting_accounts.iter().cloned());
+        }
+        result
+    }
+
+    /// Same as `process` but panics if the instruction fails
+    pub fn process_ok(&mut self, instruction: &Instruction) -> InstructionResult {
+        let result = self.process(instruction);
+        assert!(
+            !result.program_result.is_err(),
+            "instruction failed: {:?}",
+            result.program_result
+        );
+        result
+    }
+}
+
+fn load_program_elf(name: &str) -> Vec<u8> {
+    let file_name = format!("{name}.so");
+    let dirs = std::env::var("SBF_OUT_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy")]);
+
+    let path = dirs
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("{file_name} not found, run `make build` first"));
+    std::fs::read(path).expect("could not read program")
+}
+
+/// the error the program fails with when it returns the given anchor error
+pub fn anchor_error(code: impl Into<u32>) -> ProgramResult {
+    ProgramResult::Failure(ProgramError::Custom(code.into()))
+}
+
+pub fn instruction(data: impl InstructionData, accounts: impl ToAccountMetas) -> Instruction {
+    Instruction {
+        program_id: polymer_prover::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn find_pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &polymer_prover::ID).0
+}
+
+pub fn internal_pda() -> Pubkey {
+    find_pda(&[b"internal"])
+}
+
+pub fn revocations_pda() -> Pubkey {
+    find_pda(&[b"revocations"])
+}
+
+pub fn chain_config_pda(chain_id: u32) -> Pubkey {
+    find_pda(&[b"chain", &chain_id.to_be_bytes()])
+}
+
+pub fn cache_pda(authority: &Pubkey) -> Pubkey {
+    find_pda(&[b"cache", authority.as_ref()])
+}
+
+pub fn result_pda(authority: &Pubkey) -> Pubkey {
+    find_pda(&[b"result", authority.as_ref()])
+}
+
+pub fn read_proof(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/instructions/test-data")
+        .join(name);
+    let contents = std::fs::read_to_string(path).expect("could not read proof file");
+    hex::decode(contents.trim().trim_start_matches("0x")).expect("invalid proof file")
+}
+
+pub fn initialize(authority: Pubkey) -> Instruction {
+    instruction(
+        polymer_prover::instruction::Initialize {
+            client_type: CLIENT_TYPE.to_string(),
+            signer_addr: hex::decode(SIGNER_ADDR).unwrap().try_into().unwrap(),
+            peptide_chain_id: PEPTIDE_CHAIN_ID,
+        },
+        polymer_prover::accounts::Initialize {
+            authority,
+            program: polymer_prover::ID,
+            internal: internal_pda(),
+            system_program: system_program::ID,
+        },
+    )
+}
+
+/// enables the given source chain, with the client type of the internal account
+pub fn set_chain_config(authority: Pubkey, chain_id: u32, enabled: bool) -> Instruction {
+    instruction(
+        polymer_prover::instruction::SetChainConfig {
+            chain_id,
+            enabled,
+            label: format!("chain {chain_id}"),
+            client_type: None,
+        },
+        polymer_prover::accounts::UpdateChainConfig {
+            authority,
+            internal: internal_pda(),
+            chain_config: chain_config_pda(chain_id),
+            system_program: system_program::ID,
+        },
+    )
+}
+
+pub fn create_accounts(authority: Pubkey) -> Instruction {
+    instruction(
+        polymer_prover::instruction::CreateAccounts,
+        polymer_prover::accounts::CreateAccounts {
+            authority,
+            cache_account: cache_pda(&authority),
+            result_account: result_pda(&authority),
+            system_program: system_program::ID,
+        },
+    )
+}
+
+pub fn load_proof(authority: Pubkey, proof_chunk: &[u8]) -> Instruction {
+    instruction(
+        polymer_prover::instruction::LoadProof {
+            proof_chunk: proof_chunk.to_vec(),
+        },
+        polymer_prover::accounts::LoadProof {
+            authority,
+            cache_account: cache_pda(&authority),
+            system_program: Some(system_program::ID),
+            internal: Some(internal_pda()),
+        },
+    )
+}
+
+/// accounts shared by the validate instructions other than validate_event
+pub fn validation_config(chain_id: u32) -> polymer_prover::accounts::ValidationConfig {
+    polymer_prover::accounts::ValidationConfig {
+        internal: internal_pda(),
+        revocations: revocations_pda(),
+        chain_config: chain_config_pda(chain_id),
+    }
+}
+
+pub fn validate_event(authority: Pubkey, chain_id: u32) -> Instruction {
+    let mut validate = instruction(
+        polymer_prover::instruction::ValidateEvent,
+        polymer_prover::accounts::ValidateEvent {
+            authority,
+            cache_account: cache_pda(&authority),
+            result_account: result_pda(&authority),
+            internal: internal_pda(),
+            revocations: Some(revocations_pda()),
+            chain_config: Some(chain_config_pda(chain_id)),
+            system_program: Some(system_program::ID),
+        },
+    );
+    // the internal account is only written to, to record the latest peptide height, if it's
+    // passed writable
+    validate.accounts[3].is_writable = true;
+    validate
+}
+
+/// validate_event with the accounts it took before revocations and chain configs were added
+pub fn validate_event_legacy(authority: Pubkey) -> Instruction {
+    let mut validate = validate_event(authority, 0);
+    validate.accounts.truncate(4);
+    validate.accounts[3].is_writable = false;
+    validate
+}
+
+pub fn close_accounts(authority: Pubkey) -> Instruction {
+    instruction(
+        polymer_prover::instruction::CloseAccounts,
+        polymer_prover::accounts::CloseAccounts {
+            authority,
+            cache_account: cache_pda(&authority),
+            result_account: result_pda(&authority),
+        },
+    )
+}
@@ -0,0 +1,113 @@
+//! Reports the compute units consumed by each instruction and fails if any of them goes over the
+//! budget recorded in compute-units.json. Run with RECORD_COMPUTE_UNITS=1 to record the current
+//! numbers as the new budget, after an intended change in the CU consumption
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::*;
+use polymer_prover::ValidationResultAccount;
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::BTreeMap, path::PathBuf};
+
+const CHUNK_SIZE: usize = 800;
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_compute_units() {
+    let mut harness = Harness::new();
+
+    let payer = harness.new_wallet();
+    let mut consumed = BTreeMap::new();
+
+    let result = harness.process_ok(&initialize(payer));
+    consumed.insert("initialize".to_string(), result.compute_units_consumed);
+
+    for chain_id in [OP_CHAIN_ID, ARB_CHAIN_ID] {
+        harness.process_ok(&set_chain_config(payer, chain_id, true));
+    }
+
+    for (fixture, chain_id) in [("op", OP_CHAIN_ID), ("arb", ARB_CHAIN_ID)] {
+        let user = harness.new_wallet();
+        for (name, units) in run_fixture(&mut harness, user, fixture, chain_id) {
+            consumed.insert(format!("{fixture}/{name}"), units);
+        }
+    }
+
+    check_budget(&consumed);
+}
+
+/// runs the whole flow for the given proof and returns the units consumed by each instruction. For
+/// load_proof, it's the most expensive of the chunks
+fn run_fixture(harness: &mut Harness, user: Pubkey, fixture: &str, chain_id: u32) -> Vec<(&'static str, u64)> {
+    let proof = read_proof(&format!("{fixture}-proof-v2.hex"));
+
+    let create = harness.process_ok(&create_accounts(user));
+
+    let load = proof
+        .chunks(CHUNK_SIZE)
+        .map(|chunk| harness.process_ok(&load_proof(user, chunk)).compute_units_consumed)
+        .max()
+        .unwrap();
+
+    let validate = harness.process_ok(&validate_event(user, chain_id));
+    let result = harness.account(&result_pda(&user));
+    let result = ValidationResultAccount::try_deserialize(&mut result.data.as_slice()).unwrap();
+    assert!(
+        result.is_valid,
+        "{fixture} proof is not valid: {}",
+        result.error_message
+    );
+    assert_eq!(chain_id, result.chain_id);
+
+    let close = harness.process_ok(&close_accounts(user));
+
+    vec![
+        ("create_accounts", create.compute_units_consumed),
+        ("load_proof", load),
+        ("validate_event", validate.compute_units_consumed),
+        ("close_accounts", close.compute_units_consumed),
+    ]
+}
+
+fn check_budget(consumed: &BTreeMap<String, u64>) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/compute-units.json");
+
+    if std::env::var("RECORD_COMPUTE_UNITS").is_ok() {
+        let json = serde_json::to_string_pretty(consumed).unwrap();
+        std::fs::write(&path, json + "\n").expect("could not write the budget");
+        println!("compute units recorded in {}", path.display());
+    }
+
+    let budget: BTreeMap<String, u64> =
+        serde_json::from_str(&std::fs::read_to_string(&path).expect("could not read the budget")).unwrap();
+
+    println!("{:<24} {:>10} {:>10}", "instruction", "consumed", "budget");
+    let mut over_budget = vec![];
+    let mut missing = vec![];
+    for (name, units) in consumed {
+        let limit = budget.get(name);
+        println!(
+            "{:<24} {:>10} {:>10}",
+            name,
+            units,
+            limit.map_or("-".to_string(), |limit| limit.to_string())
+        );
+        match limit {
+            Some(limit) if units > limit => over_budget.push(name.as_str()),
+            Some(_) => {}
+            None => missing.push(name.as_str()),
+        }
+    }
+
+    assert!(
+        missing.is_empty(),
+        "no compute unit budget for: {}. Record it with RECORD_COMPUTE_UNITS=1",
+        missing.join(", ")
+    );
+    assert!(
+        over_budget.is_empty(),
+        "over the compute unit budget: {}. Record the new budget with RECORD_COMPUTE_UNITS=1 if it's expected",
+        over_budget.join(", ")
+    );
+}
@@ -0,0 +1,1140 @@
+//! Account level behaviour of the program, run with mollusk against the compiled program. The
+//! tests are ignored by default since they need the program built with `make build`, `make test`
+//! runs them
+
+mod common;
+
+use anchor_lang::{error::ErrorCode as AnchorErrorCode, AccountDeserialize, AnchorDeserialize, Discriminator, Space};
+use common::*;
+use polymer_prover::instructions::{
+    parse_event::EthAddress,
+    proof::{EventLocation, Proof},
+    revocation::Revocation,
+};
+use polymer_prover::testing::{
+    mock_state_tree::MockStateTree,
+    proof_builder::{eth_address, SecretKey},
+};
+use polymer_prover::{
+    ChainConfigAccount, ConsumedEventAccount, ErrorCode, InternalAccount, ProofCacheAccount, StateRootAccount,
+    ValidationResultAccount, ValidationReturn,
+};
+use solana_sdk::{account::Account, instruction::AccountMeta, pubkey::Pubkey, system_program};
+
+/// initializes the program and returns the harness along with the authority
+fn setup() -> (Harness, Pubkey) {
+    let mut harness = Harness::new();
+    let authority = harness.new_wallet();
+    harness.process_ok(&initialize(authority));
+    for chain_id in [OP_CHAIN_ID, ARB_CHAIN_ID] {
+        harness.process_ok(&set_chain_config(authority, chain_id, true));
+    }
+    (harness, authority)
+}
+
+/// creates the cache and result accounts for a new user
+fn new_user(harness: &mut Harness) -> Pubkey {
+    let user = harness.new_wallet();
+    harness.process_ok(&create_accounts(user));
+    user
+}
+
+fn cache(harness: &Harness, user: &Pubkey) -> ProofCacheAccount {
+    let account = harness.account(&cache_pda(user));
+    ProofCacheAccount::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+fn result(harness: &Harness, user: &Pubkey) -> ValidationResultAccount {
+    let account = harness.account(&result_pda(user));
+    ValidationResultAccount::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_initialize() {
+    let (harness, authority) = setup();
+
+    let account = harness.account(&internal_pda());
+    let internal = InternalAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(authority, internal.authority);
+    assert_eq!(CLIENT_TYPE, internal.client_type);
+    assert_eq!(hex::decode(SIGNER_ADDR).unwrap(), internal.signer_addr);
+    assert_eq!(PEPTIDE_CHAIN_ID, internal.peptide_chain_id);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_initialize_requires_program_signature() {
+    let mut harness = Harness::new();
+    let authority = harness.new_wallet();
+
+    let mut instruction = initialize(authority);
+    instruction.accounts[1].is_signer = false;
+    let result = harness.process(&instruction);
+    assert_eq!(anchor_error(AnchorErrorCode::AccountNotSigner), result.program_result);
+
+    // some other signer can't stand in for the program either
+    let impostor = harness.new_wallet();
+    instruction.accounts[1] = AccountMeta::new_readonly(impostor, true);
+    let result = harness.process(&instruction);
+    assert_eq!(anchor_error(AnchorErrorCode::ConstraintAddress), result.program_result);
+
+    assert_eq!(0, harness.account(&internal_pda()).lamports);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_reinitialize_fails() {
+    let (mut harness, _) = setup();
+
+    let other = harness.new_wallet();
+    let result = harness.process(&initialize(other));
+    assert!(result.program_result.is_err());
+
+    let account = harness.account(&internal_pda());
+    let internal = InternalAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_ne!(other, internal.authority);
+}
+
+/// internal account as the first version of the program created it, which is 104 bytes long
+fn legacy_internal_account(harness: &Harness, authority: &Pubkey) -> Account {
+    let mut data = InternalAccount::DISCRIMINATOR.to_vec();
+    data.extend(authority.as_ref());
+    data.extend((CLIENT_TYPE.len() as u32).to_le_bytes());
+    data.extend(CLIENT_TYPE.as_bytes());
+    data.extend(hex::decode(SIGNER_ADDR).unwrap());
+    data.extend(PEPTIDE_CHAIN_ID.to_le_bytes());
+    data.resize(104, 0);
+
+    Account {
+        lamports: harness.mollusk.sysvars.rent.minimum_balance(data.len()),
+        data,
+        owner: polymer_prover::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_migrate_internal() {
+    let mut harness = Harness::new();
+    let authority = harness.new_wallet();
+    let legacy = legacy_internal_account(&harness, &authority);
+    harness.accounts.insert(internal_pda(), legacy);
+
+    // the fields added since don't fit, so the account can't be used until it's migrated
+    let failed = harness.process(&set_chain_config(authority, OP_CHAIN_ID, true));
+    assert_eq!(
+        anchor_error(AnchorErrorCode::AccountDidNotDeserialize),
+        failed.program_result
+    );
+
+    let migrate = |authority| {
+        instruction(
+            polymer_prover::instruction::MigrateInternal,
+            polymer_prover::accounts::MigrateInternal {
+                authority,
+                internal: internal_pda(),
+                system_program: system_program::ID,
+            },
+        )
+    };
+
+    let stranger = harness.new_wallet();
+    let failed = harness.process(&migrate(stranger));
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), failed.program_result);
+
+    harness.process_ok(&migrate(authority));
+    let migrated = harness.account(&internal_pda());
+    assert_eq!(
+        InternalAccount::DISCRIMINATOR.len() + InternalAccount::INIT_SPACE,
+        migrated.data.len()
+    );
+    assert_eq!(
+        harness.mollusk.sysvars.rent.minimum_balance(migrated.data.len()),
+        migrated.lamports
+    );
+
+    let internal = InternalAccount::try_deserialize(&mut migrated.data.as_slice()).unwrap();
+    assert_eq!(authority, internal.authority);
+    assert_eq!(CLIENT_TYPE, internal.client_type);
+    assert_eq!(hex::decode(SIGNER_ADDR).unwrap(), internal.signer_addr);
+    assert_eq!(PEPTIDE_CHAIN_ID, internal.peptide_chain_id);
+    assert!(internal.signers.is_empty());
+    assert_eq!(None, internal.pending_authority);
+    assert_eq!(0, internal.latest_peptide_height);
+    assert_eq!(0, internal.min_accepted_height());
+    assert!(!internal.paused);
+
+    // migrating again changes nothing
+    harness.process_ok(&migrate(authority));
+    assert_eq!(migrated, harness.account(&internal_pda()));
+
+    harness.process_ok(&set_chain_config(authority, OP_CHAIN_ID, true));
+    let user = new_user(&mut harness);
+    harness.process_ok(&load_proof(user, &read_proof("op-proof-v2.hex")));
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+    assert!(result(&harness, &user).is_valid);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_update_config_requires_authority() {
+    let (mut harness, _) = setup();
+
+    let stranger = harness.new_wallet();
+    let instruction = instruction(
+        polymer_prover::instruction::UpdateConfig {
+            client_type: String::new(),
+            signer_addr: [0; 20],
+            peptide_chain_id: 1,
+        },
+        polymer_prover::accounts::UpdateConfig {
+            authority: stranger,
+            internal: internal_pda(),
+        },
+    );
+
+    let result = harness.process(&instruction);
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), result.program_result);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_update_config_with_signer_set() {
+    let (mut harness, authority) = setup();
+    let signer_addr: [u8; 20] = hex::decode(SIGNER_ADDR).unwrap().try_into().unwrap();
+
+    let update_config = |signer_addr| {
+        instruction(
+            polymer_prover::instruction::UpdateConfig {
+                client_type: CLIENT_TYPE.to_string(),
+                signer_addr,
+                peptide_chain_id: PEPTIDE_CHAIN_ID,
+            },
+            polymer_prover::accounts::UpdateConfig {
+                authority,
+                internal: internal_pda(),
+            },
+        )
+    };
+
+    harness.process_ok(&update_config([1; 20]));
+    harness.process_ok(&update_config(signer_addr));
+
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::UpdateSigners {
+            signers: vec![signer_addr, [2; 20]],
+            threshold: 1,
+        },
+        polymer_prover::accounts::UpdateSigners {
+            authority,
+            internal: internal_pda(),
+        },
+    ));
+
+    // the signer address is ignored once there's a signer set, so it can't be rotated anymore
+    let failed = harness.process(&update_config([1; 20]));
+    assert_eq!(anchor_error(ErrorCode::SignerSetConfigured), failed.program_result);
+
+    // the rest of the config can still be updated
+    harness.process_ok(&update_config(signer_addr));
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_validate_event() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    harness.process_ok(&load_proof(user, &proof[..800]));
+    harness.process_ok(&load_proof(user, &proof[800..]));
+    assert_eq!(proof, cache(&harness, &user).cache);
+
+    let validation = harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+
+    let result = result(&harness, &user);
+    assert!(result.is_valid);
+    assert_eq!(0, result.error_code);
+    assert_eq!(OP_CHAIN_ID, result.chain_id);
+
+    let returned = ValidationReturn::try_from_slice(&validation.return_data).unwrap();
+    assert_eq!(ValidationReturn::from(&result), returned);
+
+    assert!(cache(&harness, &user).cache.is_empty());
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_stale_peptide_height() {
+    let (mut harness, authority) = setup();
+    let user = new_user(&mut harness);
+    let op_proof = read_proof("op-proof-v2.hex");
+    let arb_proof = read_proof("arb-proof-v2.hex");
+
+    let update_height_limits = |max_peptide_height_lag, min_peptide_height| {
+        instruction(
+            polymer_prover::instruction::UpdateHeightLimits {
+                max_peptide_height_lag,
+                min_peptide_height,
+            },
+            polymer_prover::accounts::UpdateHeightLimits {
+                authority,
+                internal: internal_pda(),
+            },
+        )
+    };
+    let validate = |harness: &mut Harness, proof: &[u8], chain_id| {
+        for chunk in proof.chunks(800) {
+            harness.process_ok(&load_proof(user, chunk));
+        }
+        harness.process_ok(&validate_event(user, chain_id));
+        result(harness, &user)
+    };
+
+    // the op proof is signed at peptide height 3130134
+    harness.process_ok(&update_height_limits(0, 3_130_135));
+    let stale = validate(&mut harness, &op_proof, OP_CHAIN_ID);
+    assert!(!stale.is_valid);
+    assert_eq!(u32::from(ErrorCode::StalePeptideHeight), stale.error_code as u32);
+
+    harness.process_ok(&update_height_limits(0, 3_130_134));
+    assert!(validate(&mut harness, &op_proof, OP_CHAIN_ID).is_valid);
+
+    let account = harness.account(&internal_pda());
+    let internal = InternalAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(3_130_134, internal.latest_peptide_height);
+
+    // the arb proof is signed at peptide height 2455189, way behind the op one
+    harness.process_ok(&update_height_limits(100_000, 0));
+    let stale = validate(&mut harness, &arb_proof, ARB_CHAIN_ID);
+    assert_eq!(
+        "stale peptide height: got 2455189, at least 3030134 is needed",
+        stale.error_message
+    );
+
+    harness.process_ok(&update_height_limits(0, 0));
+    assert!(validate(&mut harness, &arb_proof, ARB_CHAIN_ID).is_valid);
+
+    // an older proof doesn't move the latest height back
+    let account = harness.account(&internal_pda());
+    let internal = InternalAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(3_130_134, internal.latest_peptide_height);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_revoked_state_root() {
+    let (mut harness, authority) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+    let revocation = Revocation::StateRoot {
+        app_hash: proof[0..32].try_into().unwrap(),
+    };
+
+    let accounts = |authority| polymer_prover::accounts::UpdateRevocations {
+        authority,
+        internal: internal_pda(),
+        revocations: revocations_pda(),
+        system_program: system_program::ID,
+    };
+    let add = |authority, revocation| {
+        instruction(
+            polymer_prover::instruction::AddRevocation { revocation },
+            accounts(authority),
+        )
+    };
+    let remove = |revocation| {
+        instruction(
+            polymer_prover::instruction::RemoveRevocation { revocation },
+            accounts(authority),
+        )
+    };
+    let validate = |harness: &mut Harness| {
+        for chunk in proof.chunks(800) {
+            harness.process_ok(&load_proof(user, chunk));
+        }
+        harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+        result(harness, &user)
+    };
+
+    // the revocations account doesn't exist until the first revocation
+    assert!(validate(&mut harness).is_valid);
+
+    let other = harness.new_wallet();
+    let result = harness.process(&add(other, revocation));
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), result.program_result);
+
+    harness.process_ok(&add(authority, revocation));
+    let revoked = validate(&mut harness);
+    assert!(!revoked.is_valid);
+    assert_eq!(u32::from(ErrorCode::RevokedStateRoot), revoked.error_code as u32);
+
+    let result = harness.process(&add(authority, revocation));
+    assert_eq!(anchor_error(ErrorCode::DuplicateRevocation), result.program_result);
+
+    let backwards = Revocation::HeightRange { from: 2, to: 1 };
+    let result = harness.process(&add(authority, backwards));
+    assert_eq!(anchor_error(ErrorCode::InvalidRevocation), result.program_result);
+
+    harness.process_ok(&remove(revocation));
+    assert!(validate(&mut harness).is_valid);
+
+    let result = harness.process(&remove(revocation));
+    assert_eq!(anchor_error(ErrorCode::RevocationNotFound), result.program_result);
+
+    // the op proof is signed at peptide height 3130134
+    harness.process_ok(&add(
+        authority,
+        Revocation::HeightRange {
+            from: 3_130_134,
+            to: 3_130_200,
+        },
+    ));
+    assert_eq!(
+        u32::from(ErrorCode::RevokedStateRoot),
+        validate(&mut harness).error_code as u32
+    );
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_chain_config() {
+    let (mut harness, authority) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    let configure = |authority, enabled, label: &str, client_type: Option<&str>| {
+        instruction(
+            polymer_prover::instruction::SetChainConfig {
+                chain_id: OP_CHAIN_ID,
+                enabled,
+                label: label.to_string(),
+                client_type: client_type.map(str::to_string),
+            },
+            polymer_prover::accounts::UpdateChainConfig {
+                authority,
+                internal: internal_pda(),
+                chain_config: chain_config_pda(OP_CHAIN_ID),
+                system_program: system_program::ID,
+            },
+        )
+    };
+    let validate = |harness: &mut Harness, chain_id| {
+        harness.process_ok(&load_proof(user, &proof));
+        harness.process_ok(&validate_event(user, chain_id));
+        result(harness, &user)
+    };
+
+    harness.process_ok(&configure(authority, true, "Optimism Sepolia", None));
+    let account = harness.account(&chain_config_pda(OP_CHAIN_ID));
+    let config = ChainConfigAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(OP_CHAIN_ID, config.chain_id);
+    assert!(config.enabled);
+    assert_eq!("Optimism Sepolia", config.label);
+    assert!(validate(&mut harness, OP_CHAIN_ID).is_valid);
+
+    // the config of another chain doesn't stand in for the proof's one
+    let unsupported = validate(&mut harness, ARB_CHAIN_ID);
+    assert!(!unsupported.is_valid);
+    assert_eq!(u32::from(ErrorCode::UnsupportedChain), unsupported.error_code as u32);
+    assert_eq!("unsupported source chain: 11155420", unsupported.error_message);
+
+    harness.process_ok(&configure(authority, false, "Optimism Sepolia", None));
+    let disabled = validate(&mut harness, OP_CHAIN_ID);
+    assert_eq!(u32::from(ErrorCode::UnsupportedChain), disabled.error_code as u32);
+
+    // the event is stored under a different key with another client type
+    harness.process_ok(&configure(authority, true, "Optimism Sepolia", Some("other_api")));
+    let other_client = validate(&mut harness, OP_CHAIN_ID);
+    assert_eq!(u32::from(ErrorCode::InvalidStateRoot), other_client.error_code as u32);
+
+    let stranger = harness.new_wallet();
+    let failed = harness.process(&configure(stranger, true, "Optimism Sepolia", None));
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), failed.program_result);
+
+    let failed = harness.process(&configure(authority, true, &"x".repeat(33), None));
+    assert_eq!(anchor_error(ErrorCode::ChainLabelTooLong), failed.program_result);
+
+    let rent = harness.account(&chain_config_pda(OP_CHAIN_ID)).lamports;
+    let before = harness.account(&authority).lamports;
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::RemoveChainConfig { chain_id: OP_CHAIN_ID },
+        polymer_prover::accounts::RemoveChainConfig {
+            authority,
+            internal: internal_pda(),
+            chain_config: chain_config_pda(OP_CHAIN_ID),
+        },
+    ));
+    assert_eq!(before + rent, harness.account(&authority).lamports);
+
+    let removed = validate(&mut harness, OP_CHAIN_ID);
+    assert_eq!(u32::from(ErrorCode::UnsupportedChain), removed.error_code as u32);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_validate_event_with_checkpoint() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let submitter = harness.new_wallet();
+    let proof = read_proof("op-proof-v2.hex");
+    let app_hash: [u8; 32] = proof[0..32].try_into().unwrap();
+    let peptide_height = 3_130_134u64;
+    let state_root = find_pda(&[b"state_root", &peptide_height.to_be_bytes()]);
+
+    let submit = |signature: &[u8]| {
+        instruction(
+            polymer_prover::instruction::SubmitStateRoot {
+                app_hash,
+                peptide_height,
+                signatures: vec![signature.try_into().unwrap()],
+            },
+            polymer_prover::accounts::SubmitStateRoot {
+                payer: submitter,
+                state_root,
+                internal: internal_pda(),
+                revocations: revocations_pda(),
+                system_program: system_program::ID,
+            },
+        )
+    };
+
+    let mut forged = proof[32..97].to_vec();
+    forged[3] ^= 1;
+    let submitted = harness.process(&submit(&forged));
+    assert_eq!(anchor_error(ErrorCode::UnknownSigner), submitted.program_result);
+
+    harness.process_ok(&submit(&proof[32..97]));
+    let account = harness.account(&state_root);
+    let stored = StateRootAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(app_hash, stored.app_hash);
+    assert_eq!(peptide_height, stored.peptide_height);
+
+    // a state root can only be submitted once per height
+    assert!(harness.process(&submit(&proof[32..97])).program_result.is_err());
+
+    let validate = instruction(
+        polymer_prover::instruction::ValidateEventWithCheckpoint,
+        polymer_prover::accounts::ValidateEventWithCheckpoint {
+            authority: user,
+            cache_account: cache_pda(&user),
+            result_account: result_pda(&user),
+            config: validation_config(OP_CHAIN_ID),
+            state_root,
+            system_program: system_program::ID,
+        },
+    );
+
+    // the checkpoint proof is the v2 one without the state root and attestation
+    let mut checkpoint = b"PLYP\x03".to_vec();
+    checkpoint.extend(&proof[97..]);
+    checkpoint[5 + 29..5 + 31].copy_from_slice(&(308u16 - 97).to_be_bytes());
+
+    for proof in [&proof, &checkpoint] {
+        harness.process_ok(&load_proof(user, proof));
+        harness.process_ok(&validate);
+        let result = result(&harness, &user);
+        assert!(result.is_valid, "{}", result.error_message);
+        assert_eq!(OP_CHAIN_ID, result.chain_id);
+    }
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_close_state_root() {
+    let (mut harness, authority) = setup();
+    let submitter = harness.new_wallet();
+    let proof = read_proof("op-proof-v2.hex");
+    let app_hash: [u8; 32] = proof[0..32].try_into().unwrap();
+    let peptide_height = 3_130_134u64;
+    let state_root = find_pda(&[b"state_root", &peptide_height.to_be_bytes()]);
+
+    let submit = instruction(
+        polymer_prover::instruction::SubmitStateRoot {
+            app_hash,
+            peptide_height,
+            signatures: vec![proof[32..97].try_into().unwrap()],
+        },
+        polymer_prover::accounts::SubmitStateRoot {
+            payer: submitter,
+            state_root,
+            internal: internal_pda(),
+            revocations: revocations_pda(),
+            system_program: system_program::ID,
+        },
+    );
+    let close = |signer, submitter| {
+        instruction(
+            polymer_prover::instruction::CloseStateRoot { peptide_height },
+            polymer_prover::accounts::CloseStateRoot {
+                authority: signer,
+                state_root,
+                submitter,
+                internal: internal_pda(),
+                revocations: revocations_pda(),
+            },
+        )
+    };
+
+    let before = harness.account(&submitter).lamports;
+    harness.process_ok(&submit);
+    let rent = harness.account(&state_root).lamports;
+    assert_eq!(before - rent, harness.account(&submitter).lamports);
+
+    let account = harness.account(&state_root);
+    let stored = StateRootAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(submitter, stored.submitter);
+
+    // only the submitter can close it at any time, and the rent goes back to them
+    let stranger = harness.new_wallet();
+    let failed = harness.process(&close(stranger, submitter));
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), failed.program_result);
+    let failed = harness.process(&close(submitter, stranger));
+    assert_eq!(anchor_error(AnchorErrorCode::ConstraintHasOne), failed.program_result);
+
+    harness.process_ok(&close(submitter, submitter));
+    assert_eq!(0, harness.account(&state_root).lamports);
+    assert_eq!(before, harness.account(&submitter).lamports);
+
+    // the authority only once it's below the minimum peptide height
+    harness.process_ok(&submit);
+    let failed = harness.process(&close(authority, submitter));
+    assert_eq!(anchor_error(ErrorCode::StateRootInUse), failed.program_result);
+
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::UpdateHeightLimits {
+            max_peptide_height_lag: 0,
+            min_peptide_height: peptide_height + 1,
+        },
+        polymer_prover::accounts::UpdateHeightLimits {
+            authority,
+            internal: internal_pda(),
+        },
+    ));
+    harness.process_ok(&close(authority, submitter));
+    assert_eq!(0, harness.account(&state_root).lamports);
+    assert_eq!(before, harness.account(&submitter).lamports);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_two_users() {
+    let (mut harness, _) = setup();
+    let user0 = new_user(&mut harness);
+    let user1 = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    // interleave the chunks, each user has its own cache
+    harness.process_ok(&load_proof(user0, &proof[..800]));
+    harness.process_ok(&load_proof(user1, &proof[..700]));
+    assert_eq!(&proof[..800], cache(&harness, &user0).cache);
+    assert_eq!(&proof[..700], cache(&harness, &user1).cache);
+
+    harness.process_ok(&load_proof(user0, &proof[800..]));
+    harness.process_ok(&load_proof(user1, &proof[700..]));
+
+    harness.process_ok(&validate_event(user0, OP_CHAIN_ID));
+    harness.process_ok(&validate_event(user1, OP_CHAIN_ID));
+
+    assert!(result(&harness, &user0).is_valid);
+    assert!(result(&harness, &user1).is_valid);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_accounts_of_other_users_are_rejected() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let other = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    harness.process_ok(&load_proof(other, &proof[..800]));
+
+    // the cache account is derived from the authority, so nobody can write to someone else's
+    let mut instruction = load_proof(user, &proof[800..]);
+    instruction.accounts[1].pubkey = cache_pda(&other);
+    let result = harness.process(&instruction);
+    assert_eq!(anchor_error(AnchorErrorCode::ConstraintSeeds), result.program_result);
+
+    let mut instruction = validate_event(user, OP_CHAIN_ID);
+    instruction.accounts[2].pubkey = result_pda(&other);
+    let result = harness.process(&instruction);
+    assert_eq!(anchor_error(AnchorErrorCode::ConstraintSeeds), result.program_result);
+
+    assert_eq!(&proof[..800], cache(&harness, &other).cache);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_recovers_from_an_error() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    // an incomplete proof is stored as an invalid result, and the cache is cleared
+    harness.process_ok(&load_proof(user, &proof[..700]));
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+
+    let invalid = result(&harness, &user);
+    assert!(!invalid.is_valid);
+    assert_eq!("invalid membership proof: can't read path", invalid.error_message);
+    assert_eq!(u32::from(ErrorCode::InvalidMembershipProof), invalid.error_code as u32);
+    assert!(cache(&harness, &user).cache.is_empty());
+
+    harness.process_ok(&load_proof(user, &proof[..700]));
+    harness.process_ok(&load_proof(user, &proof[700..]));
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+    assert!(result(&harness, &user).is_valid);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_cache_grows_on_demand() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let chunk = [1u8; 700];
+
+    // a new cache fits proofs of the default capacity
+    let before = harness.account(&cache_pda(&user));
+    assert_eq!(
+        ProofCacheAccount::DISCRIMINATOR.len() + ProofCacheAccount::INIT_SPACE,
+        before.data.len()
+    );
+    for _ in 0..4 {
+        harness.process_ok(&load_proof(user, &chunk));
+    }
+    assert_eq!(before.data.len(), harness.account(&cache_pda(&user)).data.len());
+
+    // a longer proof doesn't fit, so the account grows, paid by the user
+    let user_before = harness.account(&user).lamports;
+    harness.process_ok(&load_proof(user, &chunk));
+
+    let after = harness.account(&cache_pda(&user));
+    assert_eq!(ProofCacheAccount::space_for(5 * chunk.len()), after.data.len());
+    assert_eq!(
+        after.lamports - before.lamports,
+        user_before - harness.account(&user).lamports
+    );
+    assert_eq!(5 * chunk.len(), cache(&harness, &user).cache.len());
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_load_proof_without_system_program() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let chunk = [1u8; 1000];
+
+    // callers of older versions only pass the authority and the cache
+    let mut load = load_proof(user, &chunk);
+    load.accounts.truncate(2);
+    for _ in 0..3 {
+        harness.process_ok(&load);
+    }
+    assert_eq!(3 * chunk.len(), cache(&harness, &user).cache.len());
+
+    // but the cache can't grow without the system program
+    let failed = harness.process(&load);
+    assert_eq!(
+        anchor_error(AnchorErrorCode::AccountNotEnoughKeys),
+        failed.program_result
+    );
+    harness.process_ok(&load_proof(user, &chunk));
+    assert_eq!(4 * chunk.len(), cache(&harness, &user).cache.len());
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_large_proof() {
+    let (mut harness, authority) = setup();
+    let user = new_user(&mut harness);
+
+    // an event with 20 KiB of data, signed by a key of our own
+    let key = SecretKey::parse(&[7; 32]).unwrap();
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::UpdateConfig {
+            client_type: CLIENT_TYPE.to_string(),
+            signer_addr: *eth_address(&key).as_bytes(),
+            peptide_chain_id: PEPTIDE_CHAIN_ID,
+        },
+        polymer_prover::accounts::UpdateConfig {
+            authority,
+            internal: internal_pda(),
+        },
+    ));
+
+    let location = |log_index| EventLocation {
+        chain_id: OP_CHAIN_ID,
+        block_number: 1,
+        tx_index: 0,
+        log_index,
+    };
+    let data = vec![2; 20 * 1024];
+    let mut tree = MockStateTree::new(CLIENT_TYPE, PEPTIDE_CHAIN_ID, 1);
+    tree.insert(location(0), EthAddress::from_hex("0xabcd"), vec![[1; 32]], data.clone());
+    tree.insert(location(1), EthAddress::from_hex("0xabcd"), vec![[1; 32]], vec![3; 10]);
+    let proof = tree.proof(&location(0), &[key]).unwrap();
+
+    let load_at = |offset: usize, chunk: &[u8]| {
+        instruction(
+            polymer_prover::instruction::LoadProofAt {
+                offset: offset as u32,
+                total_len: proof.len() as u32,
+                proof_chunk: chunk.to_vec(),
+            },
+            polymer_prover::accounts::LoadProof {
+                authority: user,
+                cache_account: cache_pda(&user),
+                system_program: Some(system_program::ID),
+                internal: Some(internal_pda()),
+            },
+        )
+    };
+
+    // the accounts can't grow that much in one instruction
+    let failed = harness.process(&load_at(0, &proof[..900]));
+    assert_eq!(anchor_error(ErrorCode::AccountTooSmall), failed.program_result);
+
+    let grow = instruction(
+        polymer_prover::instruction::GrowAccounts {
+            capacity: proof.len() as u32,
+        },
+        polymer_prover::accounts::GrowAccounts {
+            authority: user,
+            cache_account: cache_pda(&user),
+            result_account: result_pda(&user),
+            system_program: system_program::ID,
+        },
+    );
+    harness.process_ok(&grow);
+    harness.process_ok(&grow);
+    assert_eq!(
+        ProofCacheAccount::space_for(proof.len()),
+        harness.account(&cache_pda(&user)).data.len()
+    );
+
+    for (i, chunk) in proof.chunks(900).enumerate() {
+        harness.process_ok(&load_at(i * 900, chunk));
+    }
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+
+    let result = result(&harness, &user);
+    assert!(result.is_valid, "{}", result.error_message);
+    assert_eq!(data, result.unindexed_data);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_close_accounts_refunds_authority() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+
+    let rent = harness.account(&cache_pda(&user)).lamports + harness.account(&result_pda(&user)).lamports;
+    let before = harness.account(&user).lamports;
+
+    harness.process_ok(&close_accounts(user));
+
+    assert_eq!(before + rent, harness.account(&user).lamports);
+    assert_eq!(0, harness.account(&cache_pda(&user)).lamports);
+    assert_eq!(0, harness.account(&result_pda(&user)).lamports);
+
+    // closed accounts can be created again
+    harness.process_ok(&create_accounts(user));
+    assert!(cache(&harness, &user).cache.is_empty());
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_pause() {
+    let (mut harness, authority) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    let accounts = |authority| polymer_prover::accounts::UpdatePause {
+        authority,
+        internal: internal_pda(),
+    };
+    let pause = |authority| instruction(polymer_prover::instruction::Pause, accounts(authority));
+    let unpause = instruction(polymer_prover::instruction::Unpause, accounts(authority));
+
+    let stranger = harness.new_wallet();
+    let failed = harness.process(&pause(stranger));
+    assert_eq!(anchor_error(ErrorCode::Unauthorized), failed.program_result);
+
+    harness.process_ok(&load_proof(user, &proof[..800]));
+    harness.process_ok(&pause(authority));
+
+    // proofs can't be loaded nor validated
+    let failed = harness.process(&load_proof(user, &proof[800..]));
+    assert_eq!(anchor_error(ErrorCode::ProgramPaused), failed.program_result);
+    let failed = harness.process(&validate_event(user, OP_CHAIN_ID));
+    assert_eq!(anchor_error(ErrorCode::ProgramPaused), failed.program_result);
+
+    // unless the caller is of an older version that doesn't pass the internal account
+    let mut legacy_load = load_proof(user, &proof[800..]);
+    legacy_load.accounts.truncate(3);
+    harness.process_ok(&legacy_load);
+
+    harness.process_ok(&unpause);
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+    assert!(result(&harness, &user).is_valid);
+
+    // users can still get their rent back while paused
+    harness.process_ok(&pause(authority));
+    harness.process_ok(&close_accounts(user));
+    assert_eq!(0, harness.account(&cache_pda(&user)).lamports);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_clear_cache() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    harness.process_ok(&load_proof(user, &proof[..600]));
+    assert_eq!(600, cache(&harness, &user).cache.len());
+
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::ClearProofCache,
+        polymer_prover::accounts::ClearProofCache {
+            authority: user,
+            cache_account: cache_pda(&user),
+            system_program: system_program::ID,
+        },
+    ));
+    assert!(cache(&harness, &user).cache.is_empty());
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_legacy_cache() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+
+    // older versions of the program only stored the proof, followed by whatever was left over
+    // from the previous ones
+    let mut data = ProofCacheAccount::DISCRIMINATOR.to_vec();
+    data.extend(0u32.to_le_bytes());
+    data.extend(proof.iter().rev().cycle().take(3000));
+    let legacy = Account {
+        lamports: harness.mollusk.sysvars.rent.minimum_balance(data.len()),
+        data,
+        owner: polymer_prover::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    harness.accounts.insert(cache_pda(&user), legacy);
+
+    harness.process_ok(&load_proof(user, &proof));
+    assert_eq!(proof, cache(&harness, &user).cache);
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+    assert!(result(&harness, &user).is_valid);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_slots() {
+    let (mut harness, _) = setup();
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+    let slot = 1u16;
+    let cache_account = find_pda(&[b"cache", user.as_ref(), &slot.to_be_bytes()]);
+    let result_account = find_pda(&[b"result", user.as_ref(), &slot.to_be_bytes()]);
+
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::CreateAccountsInSlot { slot },
+        polymer_prover::accounts::CreateAccountsInSlot {
+            authority: user,
+            cache_account,
+            result_account,
+            system_program: system_program::ID,
+        },
+    ));
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::GrowAccountsInSlot { slot, capacity: 5000 },
+        polymer_prover::accounts::GrowAccountsInSlot {
+            authority: user,
+            cache_account,
+            result_account,
+            system_program: system_program::ID,
+        },
+    ));
+    assert_eq!(
+        ProofCacheAccount::space_for(5000),
+        harness.account(&cache_account).data.len()
+    );
+
+    let set_expected_hash = instruction(
+        polymer_prover::instruction::SetExpectedProofHashInSlot {
+            slot,
+            expected_proof_hash: anchor_lang::solana_program::keccak::hash(&proof).0,
+        },
+        polymer_prover::accounts::SetExpectedProofHashInSlot {
+            authority: user,
+            cache_account,
+            system_program: system_program::ID,
+        },
+    );
+    harness.process_ok(&set_expected_hash);
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::LoadProofInSlot {
+            slot,
+            proof_chunk: proof[..600].to_vec(),
+        },
+        polymer_prover::accounts::LoadProofInSlot {
+            authority: user,
+            cache_account,
+            system_program: system_program::ID,
+            internal: Some(internal_pda()),
+        },
+    ));
+    let failed = harness.process(&set_expected_hash);
+    assert_eq!(anchor_error(ErrorCode::ProofAlreadyLoading), failed.program_result);
+
+    // clearing the slot leaves slot 0 alone
+    harness.process_ok(&load_proof(user, &proof[..700]));
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::ClearProofCacheInSlot { slot },
+        polymer_prover::accounts::ClearProofCacheInSlot {
+            authority: user,
+            cache_account,
+        },
+    ));
+    let cleared = harness.account(&cache_account);
+    let cleared = ProofCacheAccount::try_deserialize(&mut cleared.data.as_slice()).unwrap();
+    assert!(cleared.cache.is_empty());
+    assert_eq!(None, cleared.expected_hash);
+    assert_eq!(700, cache(&harness, &user).cache.len());
+
+    // the hash can be set again on the empty cache
+    harness.process_ok(&set_expected_hash);
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_validate_event_with_legacy_accounts() {
+    let mut harness = Harness::new();
+    let authority = harness.new_wallet();
+    harness.process_ok(&initialize(authority));
+    let user = new_user(&mut harness);
+    let proof = read_proof("op-proof-v2.hex");
+    let load = |harness: &mut Harness| {
+        harness.process_ok(&load_proof(user, &proof));
+    };
+    let latest_peptide_height = |harness: &Harness| {
+        let account = harness.account(&internal_pda());
+        InternalAccount::try_deserialize(&mut account.data.as_slice())
+            .unwrap()
+            .latest_peptide_height
+    };
+
+    // proofs from any chain are accepted until the first one is configured. The internal account
+    // is read-only, so the latest height isn't recorded
+    load(&mut harness);
+    harness.process_ok(&validate_event_legacy(user));
+    assert!(result(&harness, &user).is_valid);
+    assert_eq!(0, latest_peptide_height(&harness));
+
+    // the revocations account can't be left out once something is revoked
+    let revocation = Revocation::HeightRange { from: 1, to: 2 };
+    let accounts = || polymer_prover::accounts::UpdateRevocations {
+        authority,
+        internal: internal_pda(),
+        revocations: revocations_pda(),
+        system_program: system_program::ID,
+    };
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::AddRevocation { revocation },
+        accounts(),
+    ));
+    load(&mut harness);
+    let failed = harness.process(&validate_event_legacy(user));
+    assert_eq!(
+        anchor_error(ErrorCode::RevocationsAccountRequired),
+        failed.program_result
+    );
+    harness.process_ok(&instruction(
+        polymer_prover::instruction::RemoveRevocation { revocation },
+        accounts(),
+    ));
+
+    // nor the chain config once chains are configured. The proof is still in the cache, since
+    // the failed validation didn't clear it
+    harness.process_ok(&set_chain_config(authority, ARB_CHAIN_ID, true));
+    harness.process_ok(&validate_event_legacy(user));
+    let unsupported = result(&harness, &user);
+    assert!(!unsupported.is_valid);
+    assert_eq!(u32::from(ErrorCode::UnsupportedChain), unsupported.error_code as u32);
+
+    harness.process_ok(&set_chain_config(authority, OP_CHAIN_ID, true));
+    load(&mut harness);
+    harness.process_ok(&validate_event(user, OP_CHAIN_ID));
+    assert!(result(&harness, &user).is_valid);
+    assert_eq!(3_130_134, latest_peptide_height(&harness));
+}
+
+#[test]
+#[ignore = "needs the program built with `make build`"]
+fn test_validate_event_once() {
+    let (mut harness, _) = setup();
+    let proof = read_proof("op-proof-v2.hex");
+    let location = Proof::parse(&proof).unwrap().location;
+    let consumed_pda = find_pda(&[
+        b"consumed",
+        &location.chain_id.to_be_bytes(),
+        &location.block_number.to_be_bytes(),
+        &location.tx_index.to_be_bytes(),
+        &location.log_index.to_be_bytes(),
+    ]);
+    let validate_once = |user| {
+        instruction(
+            polymer_prover::instruction::ValidateEventOnce {
+                chain_id: location.chain_id,
+                block_number: location.block_number,
+                tx_index: location.tx_index,
+                log_index: location.log_index,
+            },
+            polymer_prover::accounts::ValidateEventOnce {
+                authority: user,
+                cache_account: cache_pda(&user),
+                result_account: result_pda(&user),
+                config: validation_config(OP_CHAIN_ID),
+                consumed_event: consumed_pda,
+                system_program: system_program::ID,
+            },
+        )
+    };
+
+    // an invalid proof is stored as an invalid result, without creating the consumed event account
+    let user0 = new_user(&mut harness);
+    harness.process_ok(&load_proof(user0, &proof[..700]));
+    harness.process_ok(&validate_once(user0));
+    assert!(!result(&harness, &user0).is_valid);
+    assert_eq!(0, harness.account(&consumed_pda).lamports);
+
+    // lamports sent to its address don't stop the event from being consumed
+    harness
+        .accounts
+        .insert(consumed_pda, Account::new(1, 0, &system_program::ID));
+
+    harness.process_ok(&load_proof(user0, &proof));
+    harness.process_ok(&validate_once(user0));
+    assert!(result(&harness, &user0).is_valid);
+
+    let account = harness.account(&consumed_pda);
+    assert_eq!(polymer_prover::ID, account.owner);
+    let consumed = ConsumedEventAccount::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert!(consumed.is_consumed);
+    assert_eq!(user0, consumed.consumed_by);
+
+    // the same event can't be consumed again, not even by a different user
+    let user1 = new_user(&mut harness);
+    harness.process_ok(&load_proof(user1, &proof));
+    let failed = harness.process(&validate_once(user1));
+    assert_eq!(anchor_error(ErrorCode::EventAlreadyConsumed), failed.program_result);
+}
@@ -14,8 +14,10 @@ import {
   SendTransactionError,
   PublicKey,
   LAMPORTS_PER_SOL,
+  Transaction,
 } from "@solana/web3.js";
 import bs58 from 'bs58';
+import { keccak_256 } from '@noble/hashes/sha3';
 import { execSync } from 'child_process';
 
 describe("localnet", () => {
@@ -34,6 +36,12 @@ describe("localnet", () => {
   const signerAddress = Buffer.from('8D3921B96A3815F403Fb3a4c7fF525969d16f9E0', 'hex');
   const peptideChainId = new anchor.BN(901);
 
+  // source chains of the op and arb proofs, configured before the tests run
+  const opChainId = 11_155_420;
+  const arbChainId = 421_614;
+  const opChainConfig = chainConfigPda(opChainId);
+  const arbChainConfig = chainConfigPda(arbChainId);
+
   const programKeypairFile: string = process.env.PROGRAM_KEYPAIR_FILE ?? "target/deploy/polymer_prover-keypair.json";
 
   before(async () => {
@@ -56,6 +64,16 @@ describe("localnet", () => {
       'create-accounts',
     )
     assert.ok(out1.includes('accounts successfully created'))
+
+    for (const [chainId, label] of [[opChainId, '"Optimism Sepolia"'], [arbChainId, '"Arbitrum Sepolia"']]) {
+      const out = runProverCtl(
+        '--keypair', bs58.encode(wallet.payer.secretKey),
+        'set-chain-config',
+        '--chain-id', chainId.toString(),
+        '--label', label.toString(),
+      )
+      assert.ok(out.includes('chain config successfully updated'))
+    }
   })
 
   it("internal accounts are set after init", async () => {
@@ -93,6 +111,358 @@ describe("localnet", () => {
     }
   });
 
+  it("fails to update config with different authority", async () => {
+    const newOwner = await generateAndFundNewSigner()
+
+    try {
+      await program.methods.updateConfig("", Array.from(Buffer.alloc(20)), new anchor.BN(1))
+        .accounts({ authority: newOwner.publicKey })
+        .signers([newOwner])
+        .rpc(confirmOptions);
+      throw new Error("update config should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "Unauthorized")
+    }
+  });
+
+  it("updates config", async () => {
+    const pda = findProgramAddress([Buffer.from("internal")], program.programId);
+    const newSignerAddress = Buffer.from('0000000000000000000000000000000000000001', 'hex');
+
+    const out0 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-config',
+      '--signer-addr', newSignerAddress.toString('hex'),
+      '--client-type', 'other_client',
+      '--peptide-chain-id', '902',
+    )
+    assert.ok(out0.includes('config successfully updated'))
+
+    const account0 = await program.account.internalAccount.fetch(pda, "confirmed");
+    assert.equal(account0.clientType, 'other_client')
+    assert.deepEqual(account0.signerAddr, Array.from(newSignerAddress))
+    assert.equal(account0.peptideChainId.toNumber(), 902)
+
+    // restore the original config so the rest of the tests can validate proofs
+    const out1 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-config',
+      '--signer-addr', signerAddress.toString('hex'),
+      '--client-type', clientType,
+      '--peptide-chain-id', peptideChainId.toString(),
+    )
+    assert.ok(out1.includes('config successfully updated'))
+
+    const account1 = await program.account.internalAccount.fetch(pda, "confirmed");
+    assert.equal(account1.clientType, clientType)
+    assert.deepEqual(account1.signerAddr, Array.from(signerAddress))
+    assert.equal(account1.peptideChainId.toNumber(), peptideChainId.toNumber())
+  });
+
+  it("transfers authority in two steps", async () => {
+    const pda = findProgramAddress([Buffer.from("internal")], program.programId);
+    const newOwner = await generateAndFundNewSigner()
+    const stranger = await generateAndFundNewSigner()
+
+    const out0 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'propose-authority',
+      '--new-authority', newOwner.publicKey.toBase58(),
+    )
+    assert.ok(out0.includes('authority successfully proposed'))
+
+    // nothing changes until the proposed authority accepts the role
+    const account0 = await program.account.internalAccount.fetch(pda, "confirmed");
+    assert.ok(account0.authority.equals(wallet.publicKey))
+    assert.ok(account0.pendingAuthority.equals(newOwner.publicKey))
+
+    try {
+      await program.methods.acceptAuthority()
+        .accounts({ pendingAuthority: stranger.publicKey })
+        .signers([stranger])
+        .rpc(confirmOptions);
+      throw new Error("accept authority should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "NotPendingAuthority")
+    }
+
+    const out1 = runProverCtl('--keypair', bs58.encode(newOwner.secretKey), 'accept-authority')
+    assert.ok(out1.includes('authority successfully transferred'))
+
+    const account1 = await program.account.internalAccount.fetch(pda, "confirmed");
+    assert.ok(account1.authority.equals(newOwner.publicKey))
+    assert.isNull(account1.pendingAuthority)
+
+    // hand it back so the rest of the tests can use the provider wallet as the authority
+    await program.methods.proposeAuthority(wallet.publicKey)
+      .accounts({ authority: newOwner.publicKey })
+      .signers([newOwner])
+      .rpc(confirmOptions);
+    await program.methods.acceptAuthority()
+      .accounts({ pendingAuthority: wallet.publicKey })
+      .rpc(confirmOptions);
+
+    const account2 = await program.account.internalAccount.fetch(pda, "confirmed");
+    assert.ok(account2.authority.equals(wallet.publicKey))
+  });
+
+  it("requires enough signers", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const otherSignerAddress = '0000000000000000000000000000000000000001';
+
+    // the proof only carries one signature so this makes it invalid
+    const out0 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-signers',
+      '--signer-addr', signerAddress.toString('hex'),
+      '--signer-addr', otherSignerAddress,
+      '--threshold', '2',
+    )
+    assert.ok(out0.includes('signers successfully updated'))
+
+    for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+      await program.methods
+        .loadProof(chunk)
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions);
+    }
+
+    const signature = await program.methods
+      .validateEvent()
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+      .signers([newSigner])
+      .rpc(confirmOptions);
+
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage('not enough signers: got 1, at least 2 are needed', tx))
+
+    // go back to a single signer so the rest of the tests can validate proofs
+    const out1 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-signers',
+      '--signer-addr', signerAddress.toString('hex'),
+      '--threshold', '1',
+    )
+    assert.ok(out1.includes('signers successfully updated'))
+  });
+
+  it("rejects stale proofs", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const internalPda = findProgramAddress([Buffer.from("internal")], program.programId);
+
+    const validate = async () => {
+      for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+        await program.methods
+          .loadProof(chunk)
+          .accounts({ authority: newSigner.publicKey })
+          .signers([newSigner])
+          .rpc(confirmOptions);
+      }
+
+      const validateEvent = await program.methods
+        .validateEvent()
+        .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+        .instruction();
+      // the latest peptide height is only recorded if the internal account is passed writable
+      validateEvent.keys.find((key) => key.pubkey.equals(internalPda))!.isWritable = true;
+
+      const signature = await provider.sendAndConfirm(
+        new Transaction().add(ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 }), validateEvent),
+        [newSigner],
+        confirmOptions,
+      );
+
+      return await provider.connection.getTransaction(signature, {
+        maxSupportedTransactionVersion: 0,
+        commitment: "confirmed",
+      });
+    }
+
+    // the proof is signed at peptide height 3130134
+    const out0 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-height-limits',
+      '--min-peptide-height', '3130135',
+    )
+    assert.ok(out0.includes('height limits successfully updated'))
+
+    const tx = await validate()
+    assert.ok(findLogMessage('stale peptide height: got 3130134, at least 3130135 is needed', tx))
+
+    const resultAccount = findProgramAddress([Buffer.from("result"), newSigner.publicKey.toBuffer()], program.programId);
+    const result = await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
+    assert.isFalse(result.isValid)
+    assert.equal(result.errorCode, errorCode("StalePeptideHeight"))
+
+    // lift the limits so the rest of the tests can validate proofs
+    const out1 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'update-height-limits',
+    )
+    assert.ok(out1.includes('height limits successfully updated'))
+
+    assert.ok(findLogMessage('proof is valid', await validate()))
+    const internal = await program.account.internalAccount.fetch(internalPda, "confirmed")
+    assert.ok(internal.latestPeptideHeight.gten(3130134))
+  });
+
+  it("rejects revoked state roots", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const revocationsPda = findProgramAddress([Buffer.from("revocations")], program.programId);
+
+    const validate = async () => {
+      for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+        await program.methods
+          .loadProof(chunk)
+          .accounts({ authority: newSigner.publicKey })
+          .signers([newSigner])
+          .rpc(confirmOptions);
+      }
+
+      await program.methods
+        .validateEvent()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+        .signers([newSigner])
+        .rpc(confirmOptions);
+
+      const resultAccount = findProgramAddress([Buffer.from("result"), newSigner.publicKey.toBuffer()], program.programId);
+      return await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
+    }
+
+    const appHash = proof.subarray(0, 32).toString('hex')
+    const out0 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'add-revocation',
+      '--app-hash', appHash,
+    )
+    assert.ok(out0.includes(`revoked state root 0x${appHash}`))
+
+    const revoked = await validate()
+    assert.isFalse(revoked.isValid)
+    assert.equal(revoked.errorCode, errorCode("RevokedStateRoot"))
+
+    const out1 = runProverCtl('--keypair', bs58.encode(wallet.payer.secretKey), 'list-revocations')
+    assert.ok(out1.includes(`state root 0x${appHash}`))
+
+    const out2 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'remove-revocation',
+      '--app-hash', appHash,
+    )
+    assert.ok(out2.includes('no longer revoked'))
+
+    const revocations = await program.account.revocationsAccount.fetch(revocationsPda, "confirmed")
+    assert.equal(0, revocations.revocations.length)
+    assert.isTrue((await validate()).isValid)
+  });
+
+  it("pauses the prover", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    // the internal account is optional when loading, and loading is only rejected if it's passed
+    const internal = findProgramAddress([Buffer.from("internal")], program.programId);
+    const loadProof = (chunk: Buffer) => program.methods
+      .loadProof(chunk)
+      .accountsPartial({ authority: newSigner.publicKey, internal })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+    const validate = () => program.methods
+      .validateEvent()
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+    const expectPaused = async (instruction: () => Promise<string>) => {
+      try {
+        await instruction()
+        throw new Error("instruction should have failed while paused");
+      }
+      catch (err: any) {
+        assert.ok(err instanceof anchor.AnchorError)
+        assert.equal(err.error.errorCode.code, "ProgramPaused")
+      }
+    }
+
+    await loadProof(proof.subarray(0, 800))
+
+    const out0 = runProverCtl('--keypair', bs58.encode(wallet.payer.secretKey), 'pause')
+    assert.ok(out0.includes('prover successfully paused'))
+
+    await expectPaused(() => loadProof(proof.subarray(800)))
+    await expectPaused(validate)
+
+    const out1 = runProverCtl('--keypair', bs58.encode(wallet.payer.secretKey), 'unpause')
+    assert.ok(out1.includes('prover successfully unpaused'))
+
+    await loadProof(proof.subarray(800))
+    await validate()
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  });
+
+  it("rejects proofs from disabled chains", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const resultAccount = findProgramAddress([Buffer.from("result"), newSigner.publicKey.toBuffer()], program.programId);
+
+    const validate = async () => {
+      for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+        await program.methods
+          .loadProof(chunk)
+          .accounts({ authority: newSigner.publicKey })
+          .signers([newSigner])
+          .rpc(confirmOptions);
+      }
+
+      await program.methods
+        .validateEvent()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+        .signers([newSigner])
+        .rpc(confirmOptions);
+
+      return await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
+    }
+
+    const out0 = runProverCtl('--keypair', bs58.encode(wallet.payer.secretKey), 'list-chains')
+    assert.ok(out0.includes(`chain ${opChainId} (Optimism Sepolia): enabled: true`))
+    assert.ok(out0.includes(`chain ${arbChainId} (Arbitrum Sepolia): enabled: true`))
+
+    const out1 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'set-chain-config',
+      '--chain-id', opChainId.toString(),
+      '--label', '"Optimism Sepolia"',
+      '--disabled',
+    )
+    assert.ok(out1.includes('chain config successfully updated'))
+
+    const disabled = await validate()
+    assert.isFalse(disabled.isValid)
+    assert.equal(disabled.errorCode, errorCode("UnsupportedChain"))
+    assert.equal(disabled.errorMessage, `unsupported source chain: ${opChainId}`)
+
+    const out2 = runProverCtl(
+      '--keypair', bs58.encode(wallet.payer.secretKey),
+      'set-chain-config',
+      '--chain-id', opChainId.toString(),
+      '--label', '"Optimism Sepolia"',
+    )
+    assert.ok(out2.includes('chain config successfully updated'))
+
+    const config = await program.account.chainConfigAccount.fetch(opChainConfig, "confirmed")
+    assert.isTrue(config.enabled)
+    assert.equal(config.label, "Optimism Sepolia")
+    assert.isTrue((await validate()).isValid)
+  });
+
   // happy path to validate event. The instruction is called by a new user (different from the program's deployer)
   // it checks that the program accepts proofs in chunks and temporarily stores them in a PDA account.
   // Once all the chunks have been sent, it runs the actual event validation
@@ -124,7 +494,7 @@ describe("localnet", () => {
     const signature = await program.methods
       .validateEvent()
       .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-      .accounts({ authority: newSigner.publicKey })
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
       .signers([newSigner])
       .rpc(confirmOptions);
 
@@ -141,6 +511,378 @@ describe("localnet", () => {
 
     assert.ok(findLogMessage('proof is valid', ...txs))
     await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+
+    // the result is also set as return data, so CPI callers don't need to read the result account
+    const returnData = txs.at(-1).meta.returnData
+    assert.equal(returnData.programId, program.programId.toBase58())
+    assert.equal(Buffer.from(returnData.data[0], 'base64')[0], 1)
+  });
+
+  it("loads proof chunks at any offset", async () => {
+    const newSigner = await generateAndFundNewSigner()
+
+    const loadAt = async (offset: number) => {
+      await program.methods
+        .loadProofAt(offset, proof.length, proof.subarray(offset, offset + 500))
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    const validate = async () => {
+      return await program.methods
+        .validateEvent()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    // send the chunks out of order and retry one of them
+    await loadAt(500)
+    await loadAt(0)
+    await loadAt(500)
+
+    // the last chunk is missing so the proof can't be validated yet
+    try {
+      await validate()
+      throw new Error("validation should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "ProofIncomplete")
+    }
+
+    await loadAt(1000)
+    const signature = await validate()
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage('proof is valid', tx))
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  });
+
+  it("checks the expected proof hash", async () => {
+    const newSigner = await generateAndFundNewSigner()
+
+    // the hash is set before loading the proof, it can't be changed afterwards
+    const validateWithHash = async (hash: Uint8Array) => {
+      await program.methods
+        .clearProofCache()
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+      await program.methods
+        .setExpectedProofHash(Array.from(hash))
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+
+      for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+        await program.methods
+          .loadProof(chunk)
+          .accounts({ authority: newSigner.publicKey })
+          .signers([newSigner])
+          .rpc(confirmOptions)
+      }
+
+      try {
+        await program.methods
+          .setExpectedProofHash(Array.from(keccak_256(proof)))
+          .accounts({ authority: newSigner.publicKey })
+          .signers([newSigner])
+          .rpc(confirmOptions)
+        throw new Error("setting the hash should have failed");
+      }
+      catch (err: any) {
+        assert.ok(err instanceof anchor.AnchorError)
+        assert.equal(err.error.errorCode.code, "ProofAlreadyLoading")
+      }
+
+      return await program.methods
+        .validateEvent()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    // the hash of a different proof makes the validation fail
+    try {
+      await validateWithHash(keccak_256(proof.subarray(0, 800)))
+      throw new Error("validation should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "ProofHashMismatch")
+    }
+
+    const signature = await validateWithHash(keccak_256(proof))
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage('proof is valid', tx))
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  });
+
+  it("validates events in different slots", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const slots = [1, 2]
+
+    const slotAccounts = (slot: number) => {
+      const slotSeed = Buffer.alloc(2)
+      slotSeed.writeUInt16BE(slot)
+      return {
+        authority: newSigner.publicKey,
+        cacheAccount: findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer(), slotSeed], program.programId),
+        resultAccount: findProgramAddress([Buffer.from("result"), newSigner.publicKey.toBuffer(), slotSeed], program.programId),
+      }
+    }
+
+    // load a full proof in one slot and half of it in the other one, so they can't be mixed up
+    for (const slot of slots) {
+      await program.methods
+        .createAccountsInSlot(slot)
+        .accountsPartial(slotAccounts(slot))
+        .signers([newSigner])
+        .rpc(confirmOptions)
+
+      const chunks = slot == 1 ? [proof.subarray(0, 800), proof.subarray(800)] : [proof.subarray(0, 800)]
+      await program.methods
+        .setExpectedProofHashInSlot(slot, Array.from(keccak_256(Buffer.concat(chunks))))
+        .accountsPartial({ authority: newSigner.publicKey, cacheAccount: slotAccounts(slot).cacheAccount })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+
+      for (const chunk of chunks) {
+        await program.methods
+          .loadProofInSlot(slot, chunk)
+          .accountsPartial({ authority: newSigner.publicKey, cacheAccount: slotAccounts(slot).cacheAccount })
+          .signers([newSigner])
+          .rpc(confirmOptions)
+      }
+    }
+
+    for (const slot of slots) {
+      await program.methods
+        .validateEventInSlot(slot)
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accountsPartial({ ...slotAccounts(slot), config: { chainConfig: opChainConfig } })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    const results = await Promise.all(slots.map((slot) =>
+      program.account.validationResultAccount.fetch(slotAccounts(slot).resultAccount, "confirmed")
+    ))
+    assert.isTrue(results[0].isValid)
+    assert.equal(results[0].chainId, 11_155_420)
+    assert.isFalse(results[1].isValid)
+
+    // slot 0 is the same as the accounts without a slot, whose cache was never touched
+    const cache = await program.account.proofCacheAccount.fetch(
+      findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId), "confirmed"
+    )
+    assert.equal(0, cache.cache.length)
+
+    // a partial proof can be cleared from its slot
+    await program.methods
+      .loadProofInSlot(2, proof.subarray(0, 800))
+      .accountsPartial({ authority: newSigner.publicKey, cacheAccount: slotAccounts(2).cacheAccount })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+    await program.methods
+      .clearProofCacheInSlot(2)
+      .accountsPartial({ authority: newSigner.publicKey, cacheAccount: slotAccounts(2).cacheAccount })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+    const cleared = await program.account.proofCacheAccount.fetch(slotAccounts(2).cacheAccount, "confirmed")
+    assert.equal(0, cleared.cache.length)
+
+    for (const slot of slots) {
+      await program.methods
+        .closeAccountsInSlot(slot)
+        .accountsPartial(slotAccounts(slot))
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+  });
+
+  it("validates event in strict mode", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const cachePda = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
+
+    const validateStrict = async (chunk: Buffer) => {
+      await program.methods
+        .loadProof(chunk)
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+
+      return await program.methods
+        .validateEventStrict()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    // an incomplete proof makes the whole transaction fail, so the cache is left untouched
+    try {
+      await validateStrict(proof.subarray(0, 700))
+      throw new Error("strict validation should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "InvalidMembershipProof")
+    }
+
+    const cache = await program.account.proofCacheAccount.fetch(cachePda, "confirmed")
+    assert.equal(700, cache.cache.length)
+
+    // loading the rest of the proof makes it valid
+    const signature = await validateStrict(proof.subarray(700))
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage('proof is valid', tx))
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  });
+
+  it("validates event with the secp256k1 program", async () => {
+    const newSigner = await generateAndFundNewSigner()
+
+    for (let start = 0; start < proof.length; start += 800) {
+      await program.methods
+        .loadProof(proof.subarray(start, start + 800))
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    // without a secp256k1 program instruction in the transaction, the attestation in the proof
+    // doesn't count and the proof is not signed by anyone
+    await program.methods
+      .validateEventPrecompiled()
+      .accounts({ authority: newSigner.publicKey, config: { chainConfig: opChainConfig } })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    const resultAccount = findProgramAddress([Buffer.from("result"), newSigner.publicKey.toBuffer()], program.programId);
+    const result = await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
+    assert.isFalse(result.isValid)
+    assert.equal(result.errorCode, errorCode("NotEnoughSigners"))
+  });
+
+  it("validates event with a submitted state root", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const appHash = Array.from(proof.subarray(0, 32))
+    const peptideHeight = new anchor.BN(proof.readBigUInt64BE(101).toString())
+    const stateRoot = findProgramAddress([Buffer.from("state_root"), peptideHeight.toArrayLike(Buffer, "be", 8)], program.programId);
+
+    await program.methods
+      .submitStateRoot(appHash, peptideHeight, [Array.from(proof.subarray(32, 97))])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accountsPartial({ payer: newSigner.publicKey, stateRoot })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    const stored = await program.account.stateRootAccount.fetch(stateRoot, "confirmed")
+    assert.deepEqual(stored.appHash, appHash)
+
+    for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+      await program.methods
+        .loadProof(chunk)
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions);
+    }
+
+    const signature = await program.methods
+      .validateEventWithCheckpoint()
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accounts({ authority: newSigner.publicKey, stateRoot, config: { chainConfig: opChainConfig } })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage('proof is valid', tx))
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  });
+
+  it("validates event inline", async () => {
+    // the full proof doesn't fit in a single transaction without lookup tables, so send an
+    // incomplete one and check the error is delivered without any cache or result accounts
+    const signature = await program.methods
+      .validateEventInline(proof.subarray(0, 700))
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accounts({ chainConfig: opChainConfig })
+      .rpc(confirmOptions)
+
+    const tx = await provider.connection.getTransaction(signature, {
+      maxSupportedTransactionVersion: 0,
+      commitment: "confirmed",
+    });
+    assert.ok(findLogMessage("invalid membership proof: can't read path", tx))
+
+    // is_valid followed by the error code
+    const returnData = Buffer.from(tx.meta.returnData.data[0], 'base64')
+    assert.equal(returnData.readUInt8(0), 0)
+    assert.equal(returnData.readUInt16LE(1), errorCode("invalidMembershipProof"))
+  });
+
+  it("validates event only once", async () => {
+    const user0 = await generateAndFundNewSigner()
+    const user1 = await generateAndFundNewSigner()
+
+    // these come from the proof header: chain id, block number, tx index and log index
+    const chainId = proof.readUInt32BE(97)
+    const blockNumber = new anchor.BN(proof.subarray(109, 117))
+    const txIndex = proof.readUInt32BE(117)
+    const logIndex = proof.readUInt32BE(121)
+
+    const validateOnce = async (user: Keypair) => {
+      for (const chunk of [proof.subarray(0, 800), proof.subarray(800)]) {
+        await program.methods
+          .loadProof(chunk)
+          .accounts({ authority: user.publicKey })
+          .signers([user])
+          .rpc(confirmOptions);
+      }
+
+      const signature = await program.methods
+        .validateEventOnce(chainId, blockNumber, txIndex, logIndex)
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+        .accounts({ authority: user.publicKey, config: { chainConfig: opChainConfig } })
+        .signers([user])
+        .rpc(confirmOptions);
+
+      return await provider.connection.getTransaction(signature, {
+        maxSupportedTransactionVersion: 0,
+        commitment: "confirmed",
+      });
+    }
+
+    // the first user consumes the event
+    const tx0 = await validateOnce(user0)
+    assert.ok(findLogMessage('proof is valid', tx0))
+    await checkValidatationResult(user0, 11_155_420, 'op-event-v2.json')
+
+    // the same event can't be consumed again, not even by a different user
+    try {
+      await validateOnce(user1)
+      throw new Error("consuming the event again should have failed");
+    }
+    catch (err: any) {
+      assert.ok(err instanceof anchor.AnchorError)
+      assert.equal(err.error.errorCode.code, "EventAlreadyConsumed")
+    }
   });
 
   it("closes accounts", async () => {
@@ -203,7 +945,7 @@ describe("localnet", () => {
     const signature = await program.methods
       .validateEvent()
       .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-      .accounts({ authority: newSigner.publicKey })
+      .accounts({ authority: newSigner.publicKey, chainConfig: arbChainConfig })
       .signers([newSigner])
       .rpc(confirmOptions);
 
@@ -279,13 +1021,13 @@ describe("localnet", () => {
       program.methods
         .validateEvent()
         .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-        .accounts({ authority: user0.publicKey })
+        .accounts({ authority: user0.publicKey, chainConfig: opChainConfig })
         .signers([user0])
         .rpc(confirmOptions),
       program.methods
         .validateEvent()
         .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-        .accounts({ authority: user1.publicKey })
+        .accounts({ authority: user1.publicKey, chainConfig: opChainConfig })
         .signers([user1])
         .rpc(confirmOptions)
     ])
@@ -327,7 +1069,7 @@ describe("localnet", () => {
     const sig0 = await program.methods
       .validateEvent()
       .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-      .accounts({ authority: newSigner.publicKey })
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
       .signers([newSigner])
       .rpc(confirmOptions)
 
@@ -348,6 +1090,7 @@ describe("localnet", () => {
     const result = await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
     assert.isFalse(result.isValid)
     assert.equal(result.errorMessage, "invalid membership proof: can't read path");
+    assert.equal(result.errorCode, errorCode("invalidMembershipProof"));
     assert.equal(result.chainId, 0);
     assert.equal(Buffer.from(result.emittingContract).toString('hex'), '0000000000000000000000000000000000000000');
     assert.equal(result.topics.length, 0);
@@ -371,7 +1114,7 @@ describe("localnet", () => {
     const signature1 = await program.methods
       .validateEvent()
       .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
-      .accounts({ authority: newSigner.publicKey })
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
       .signers([newSigner])
       .rpc(confirmOptions);
     const txs = await provider.connection.getTransactions([signature1], {
@@ -386,48 +1129,92 @@ describe("localnet", () => {
   });
 
 
-  it("errors out if cache account limit is reached", async () => {
+  it("grows the cache account on demand", async () => {
     const newSigner = await generateAndFundNewSigner()
+    const cachePda = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
 
-    // the proof cache account is currently capped to 3000 bytes. So these txs should sucdeed. The data
-    // we are sending does not mater
+    // the proof cache account is created for 3000 bytes. The data we are sending does not mater
     for (let i = 0; i < 5; i++) {
-      const sig = await program.methods
+      await program.methods
         .loadProof(proof.subarray(0, 600))
         .accounts({ authority: newSigner.publicKey })
         .signers([newSigner])
         .rpc(confirmOptions)
+    }
 
-      const tx = await provider.connection.getTransaction(sig, {
-        maxSupportedTransactionVersion: 0,
-        commitment: "confirmed",
-      });
+    const before = await provider.connection.getAccountInfo(cachePda, "confirmed");
 
-      console.log(tx.meta.logMessages)
-    }
+    // now that the cache account is full, sending one more byte grows it, paid by the authority
+    await program.methods
+      .loadProof(proof.subarray(0, 1))
+      .accounts({ authority: newSigner.publicKey })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    const after = await provider.connection.getAccountInfo(cachePda, "confirmed");
+    assert.equal(after.data.length, before.data.length + 1)
+    assert.ok(after.lamports > before.lamports)
 
+    // the cache still can't hold more than the max proof length
     try {
-      // now that the cache account is full, sending one more byte will trigger an error
-      const sig = await program.methods
-        .loadProof(proof.subarray(0, 1))
+      await program.methods
+        .loadProofAt(0, 20 * 1024, proof.subarray(0, 1))
         .accounts({ authority: newSigner.publicKey })
         .signers([newSigner])
         .rpc(confirmOptions)
-
-      const tx = await provider.connection.getTransaction(sig, {
-        maxSupportedTransactionVersion: 0,
-        commitment: "confirmed",
-      });
-      console.log(tx);
-      throw new Error("loadProof should have failed");
+      throw new Error("loadProofAt should have failed");
     }
     catch (err: any) {
       assert.ok(err instanceof anchor.AnchorError)
-      assert.ok(err.logs !== undefined)
-      assert.ok(err.logs.find((log: string) => log.includes('AnchorError caused by account: cache_account')))
+      assert.equal(err.error.errorCode.code, "InvalidProofLength")
     }
   })
 
+  it("creates accounts with a given capacity", async () => {
+    const newSigner = Keypair.generate();
+    const airdrop = await provider.connection.requestAirdrop(newSigner.publicKey, 2 * LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(airdrop, "confirmed");
+
+    const cachePda = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
+
+    await program.methods
+      .createAccountsWithCapacity(10 * 1024)
+      .accounts({ authority: newSigner.publicKey })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    // accounts can't be created larger than 10 KiB, the rest is added by grow_accounts
+    const created = await provider.connection.getAccountInfo(cachePda, "confirmed");
+    assert.equal(created.data.length, 10 * 1024)
+
+    await program.methods
+      .growAccounts(10 * 1024)
+      .accounts({ authority: newSigner.publicKey })
+      .signers([newSigner])
+      .rpc(confirmOptions)
+
+    const grown = await provider.connection.getAccountInfo(cachePda, "confirmed");
+    assert.ok(grown.data.length > created.data.length)
+
+    // the proof now fits without the cache having to grow while loading
+    for (let start = 0; start < proof.length; start += 800) {
+      await program.methods
+        .loadProof(proof.subarray(start, start + 800))
+        .accounts({ authority: newSigner.publicKey })
+        .signers([newSigner])
+        .rpc(confirmOptions)
+    }
+
+    await program.methods
+      .validateEvent()
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .accounts({ authority: newSigner.publicKey, chainConfig: opChainConfig })
+      .signers([newSigner])
+      .rpc(confirmOptions);
+
+    await checkValidatationResult(newSigner, 11_155_420, 'op-event-v2.json')
+  })
+
   it("clears cache", async () => {
     const newSigner = await generateAndFundNewSigner()
     const cacheAccount = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
@@ -481,6 +1268,21 @@ describe("localnet", () => {
     assert.equal(0, cache2.cache.length)
   })
 
+  it("resizes cache", async () => {
+    const newSigner = await generateAndFundNewSigner()
+    const cacheAccount = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
+
+    const before = await provider.connection.getAccountInfo(cacheAccount, "confirmed");
+
+    // the account was just created so it already has the right size. Resizing is a no-op in that case
+    const out = runProverCtl('--keypair', bs58.encode(newSigner.secretKey), 'resize-cache')
+    assert.ok(out.includes('cache account successfully resized to 3012 bytes'))
+
+    const after = await provider.connection.getAccountInfo(cacheAccount, "confirmed");
+    assert.equal(before.data.length, after.data.length)
+    assert.equal(before.lamports, after.lamports)
+  })
+
   it("support cpi calls", async () => {
     const newSigner = await generateAndFundNewSigner()
     const cacheAccount = findProgramAddress([Buffer.from("cache"), newSigner.publicKey.toBuffer()], program.programId);
@@ -491,6 +1293,7 @@ describe("localnet", () => {
       .accounts({
         authority: newSigner.publicKey,
         cacheAccount: cacheAccount,
+        internal: findProgramAddress([Buffer.from("internal")], program.programId),
       })
       .signers([newSigner])
       .rpc(confirmOptions)
@@ -502,6 +1305,8 @@ describe("localnet", () => {
         authority: newSigner.publicKey,
         cacheAccount: cacheAccount,
         internal: findProgramAddress([Buffer.from("internal")], program.programId),
+        revocations: findProgramAddress([Buffer.from("revocations")], program.programId),
+        chainConfig: opChainConfig,
         resultAccount: resultAccount,
       })
       .signers([newSigner])
@@ -556,10 +1361,22 @@ describe("localnet", () => {
     return address
   }
 
+  function chainConfigPda(chainId: number): PublicKey {
+    const seed = Buffer.alloc(4)
+    seed.writeUInt32BE(chainId)
+    return findProgramAddress([Buffer.from("chain"), seed], program.programId)
+  }
+
+  // numeric code of the given prover error, as stored in the result account
+  function errorCode(name: string): number {
+    return program.idl.errors.find((e) => e.name.toLowerCase() === name.toLowerCase()).code
+  }
+
   async function checkValidatationResult(signer: Keypair, chainId: number, eventFileName: string) {
     const resultAccount = findProgramAddress([Buffer.from("result"), signer.publicKey.toBuffer()], program.programId);
     const result = await program.account.validationResultAccount.fetch(resultAccount, "confirmed")
     assert.isTrue(result.isValid)
+    assert.equal(result.errorCode, 0)
 
     let topics = Buffer.alloc(0);
     const expectedEvent = readEventFile(eventFileName)
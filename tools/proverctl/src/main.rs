@@ -1,5 +1,6 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use polymer_prover::instructions::revocation::Revocation;
 use solana_sdk::signature::{read_keypair_file, Keypair};
 use std::env;
 
@@ -51,11 +52,126 @@ enum Commands {
         #[arg(long)]
         peptide_chain_id: u64,
     },
+    /// grows an internal account created by an older version of the program. Run it once after
+    /// upgrading the program
+    MigrateInternal,
+    UpdateConfig {
+        #[arg(long)]
+        client_type: String,
+
+        #[arg(long)]
+        signer_addr: String,
+
+        #[arg(long)]
+        peptide_chain_id: u64,
+    },
+    UpdateSigners {
+        /// can be passed multiple times, once per signer
+        #[arg(long = "signer-addr", required = true)]
+        signer_addrs: Vec<String>,
+
+        #[arg(long)]
+        threshold: u8,
+    },
+    /// proofs below the minimum height, or lagging too far behind the latest accepted one, are
+    /// rejected. Zero disables either check
+    UpdateHeightLimits {
+        #[arg(long, default_value_t = 0)]
+        max_peptide_height_lag: u64,
+
+        #[arg(long, default_value_t = 0)]
+        min_peptide_height: u64,
+    },
+    /// revokes a state root, or every state root in a peptide height range
+    AddRevocation {
+        #[command(flatten)]
+        revocation: RevocationArgs,
+    },
+    /// lifts a revocation added with add-revocation
+    RemoveRevocation {
+        #[command(flatten)]
+        revocation: RevocationArgs,
+    },
+    ListRevocations,
+    /// configures a source chain proofs are accepted from. Proofs from other chains are rejected
+    SetChainConfig {
+        #[arg(long)]
+        chain_id: u32,
+
+        /// human readable name of the chain
+        #[arg(long)]
+        label: String,
+
+        /// client type of the chain's events, if it's not the default one
+        #[arg(long)]
+        client_type: Option<String>,
+
+        /// keeps the config but rejects the chain's proofs
+        #[arg(long)]
+        disabled: bool,
+    },
+    RemoveChainConfig {
+        #[arg(long)]
+        chain_id: u32,
+    },
+    ListChains,
+    /// stops validating proofs, and loading them for callers that pass the internal account, e.g.
+    /// if peptide or a signer key is compromised
+    Pause,
+    Unpause,
+    ProposeAuthority {
+        /// public key of the new authority, base58 encoded
+        #[arg(long)]
+        new_authority: String,
+    },
+    /// must be signed by the proposed authority
+    AcceptAuthority,
     ClearCache,
+    ResizeCache,
+    /// loads a proof into the cache account. Chunks are sent in parallel and retried on failure
+    LoadProof {
+        /// file with the hex encoded proof
+        #[arg(long)]
+        proof_file: String,
+
+        #[arg(long, default_value_t = 800)]
+        chunk_size: usize,
+    },
     CreateAccounts,
     CloseAccounts,
 }
 
+#[derive(Args)]
+struct RevocationArgs {
+    /// state root, hex encoded
+    #[arg(long, required_unless_present = "from_height", conflicts_with = "from_height")]
+    app_hash: Option<String>,
+
+    /// first peptide height of the range
+    #[arg(long, requires = "to_height")]
+    from_height: Option<u64>,
+
+    /// last peptide height of the range, inclusive
+    #[arg(long, requires = "from_height")]
+    to_height: Option<u64>,
+}
+
+impl RevocationArgs {
+    fn revocation(&self) -> Result<Revocation> {
+        if let Some(app_hash) = &self.app_hash {
+            let app_hash = hex::decode(app_hash.trim_start_matches("0x"))?;
+            let app_hash =
+                <[u8; 32]>::try_from(app_hash).map_err(|_| anyhow::anyhow!("the state root must be 32 bytes"))?;
+            return Ok(Revocation::StateRoot { app_hash });
+        }
+
+        Ok(Revocation::HeightRange {
+            from: self.from_height.unwrap_or_default(),
+            to: self.to_height.unwrap_or_default(),
+        })
+    }
+}
+
 fn main() -> Result<()> {
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "info");
@@ -64,7 +180,7 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    let signer = if cli.keypair != "" {
+    let signer = if !cli.keypair.is_empty() {
         Keypair::from_base58_string(&cli.keypair)
     } else {
         read_keypair_file(cli.keypair_path).map_err(|e| anyhow::anyhow!("Failed to read keypair: {}", e))?
@@ -77,7 +193,42 @@ fn main() -> Result<()> {
             signer_addr,
             peptide_chain_id,
         } => client.send_initialize(client_type, signer_addr, *peptide_chain_id)?,
+        Commands::MigrateInternal => client.send_migrate_internal()?,
+        Commands::UpdateConfig {
+            client_type,
+            signer_addr,
+            peptide_chain_id,
+        } => client.send_update_config(client_type, signer_addr, *peptide_chain_id)?,
+        Commands::UpdateSigners {
+            signer_addrs,
+            threshold,
+        } => client.send_update_signers(signer_addrs, *threshold)?,
+        Commands::UpdateHeightLimits {
+            max_peptide_height_lag,
+            min_peptide_height,
+        } => client.send_update_height_limits(*max_peptide_height_lag, *min_peptide_height)?,
+        Commands::AddRevocation { revocation } => client.send_add_revocation(revocation.revocation()?)?,
+        Commands::RemoveRevocation { revocation } => client.send_remove_revocation(revocation.revocation()?)?,
+        Commands::ListRevocations => client.list_revocations()?,
+        Commands::SetChainConfig {
+            chain_id,
+            label,
+            client_type,
+            disabled,
+        } => client.send_set_chain_config(*chain_id, !disabled, label, client_type.clone())?,
+        Commands::RemoveChainConfig { chain_id } => client.send_remove_chain_config(*chain_id)?,
+        Commands::ListChains => client.list_chains()?,
+        Commands::Pause => client.send_pause()?,
+        Commands::Unpause => client.send_unpause()?,
+        Commands::ProposeAuthority { new_authority } => client.send_propose_authority(new_authority)?,
+        Commands::AcceptAuthority => client.send_accept_authority()?,
         Commands::ClearCache => client.send_clear_cache()?,
+        Commands::ResizeCache => client.send_resize_cache()?,
+        Commands::LoadProof { proof_file, chunk_size } => {
+            let contents = std::fs::read_to_string(proof_file)?;
+            let proof = hex::decode(contents.trim().trim_start_matches("0x"))?;
+            client.send_load_proof(&proof, *chunk_size)?
+        }
         Commands::CreateAccounts => client.send_create_accounts()?,
         Commands::CloseAccounts => client.send_close_accounts()?,
     }
@@ -1,12 +1,21 @@
-use anchor_lang::InstructionData;
+use anchor_lang::{AccountDeserialize, Discriminator, InstructionData};
 use anyhow::{Ok, Result};
 use log::{info, warn};
 use polymer_prover::{
-    instruction::{ClearProofCache, CloseAccounts, CreateAccounts, Initialize},
-    instructions::parse_event::EthAddress,
+    instruction::{
+        AcceptAuthority, AddRevocation, ClearProofCache, CloseAccounts, CreateAccounts, GrowAccounts, Initialize,
+        LoadProofAt, MigrateInternal, Pause, ProposeAuthority, RemoveChainConfig, RemoveRevocation, ResizeCache,
+        SetChainConfig, Unpause, UpdateConfig, UpdateHeightLimits, UpdateSigners,
+    },
+    instructions::{parse_event::EthAddress, revocation::Revocation},
+    ChainConfigAccount, ProofCacheAccount, RevocationsAccount, ValidationResultAccount,
 };
 use retry::{delay::Fixed, retry, OperationResult};
-use solana_client::{rpc_client::RpcClient, rpc_config::*};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::*,
+    rpc_filter::{Memcmp, RpcFilterType},
+};
 use solana_sdk::{
     commitment_config::{CommitmentConfig, CommitmentLevel},
     instruction::{AccountMeta, Instruction},
@@ -16,7 +25,7 @@ use solana_sdk::{
     transaction::Transaction,
 };
 use solana_transaction_status_client_types::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
 
 pub struct Client {
     pub program: Keypair,
@@ -30,18 +39,18 @@ impl Client {
             read_keypair_file(program_keypair).map_err(|e| anyhow::anyhow!("Failed to read keypair: {}", e))?;
 
         let client = RpcClient::new(cluster.to_string());
-        info!("PROGRAM_ID: {}", program.pubkey().to_string());
+        info!("PROGRAM_ID: {}", program.pubkey());
         info!("PAYER: {}", payer.pubkey());
         Ok(Client { program, payer, client })
     }
 
-    pub fn send_initialize(&self, client_type: &String, signer_addr: &String, peptide_chain_id: u64) -> Result<()> {
+    pub fn send_initialize(&self, client_type: &str, signer_addr: &str, peptide_chain_id: u64) -> Result<()> {
         let data = polymer_prover::instruction::Initialize {
             client_type: client_type.to_string(),
-            signer_addr: EthAddress::from_hex(signer_addr.as_str()).as_bytes().clone(),
+            signer_addr: *EthAddress::from_hex(signer_addr).as_bytes(),
             peptide_chain_id,
         };
-        let (internal_account, _) = Pubkey::find_program_address(&[b"internal"], &self.program.pubkey());
+        let internal_account = self.find_internal_account();
         let instruction = Instruction {
             program_id: self.program.pubkey(),
             data: Initialize::data(&data),
@@ -58,6 +67,267 @@ impl Client {
         Ok(())
     }
 
+    pub fn send_migrate_internal(&self) -> Result<()> {
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: MigrateInternal.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_update_config(&self, client_type: &str, signer_addr: &str, peptide_chain_id: u64) -> Result<()> {
+        let data = UpdateConfig {
+            client_type: client_type.to_string(),
+            signer_addr: *EthAddress::from_hex(signer_addr).as_bytes(),
+            peptide_chain_id,
+        };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_update_signers(&self, signer_addrs: &[String], threshold: u8) -> Result<()> {
+        let data = UpdateSigners {
+            signers: signer_addrs
+                .iter()
+                .map(|addr| *EthAddress::from_hex(addr).as_bytes())
+                .collect(),
+            threshold,
+        };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_update_height_limits(&self, max_peptide_height_lag: u64, min_peptide_height: u64) -> Result<()> {
+        let data = UpdateHeightLimits {
+            max_peptide_height_lag,
+            min_peptide_height,
+        };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_set_chain_config(
+        &self,
+        chain_id: u32,
+        enabled: bool,
+        label: &str,
+        client_type: Option<String>,
+    ) -> Result<()> {
+        let data = SetChainConfig {
+            chain_id,
+            enabled,
+            label: label.to_string(),
+            client_type,
+        };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new_readonly(self.find_internal_account(), false),
+                AccountMeta::new(self.find_chain_config_account(chain_id), false),
+                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_remove_chain_config(&self, chain_id: u32) -> Result<()> {
+        let data = RemoveChainConfig { chain_id };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new_readonly(self.find_internal_account(), false),
+                AccountMeta::new(self.find_chain_config_account(chain_id), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    /// logs every configured source chain, found by the discriminator of their accounts
+    pub fn list_chains(&self) -> Result<()> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                ChainConfigAccount::DISCRIMINATOR.to_vec(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut chains = self
+            .client
+            .get_program_accounts_with_config(&self.program.pubkey(), config)?
+            .into_iter()
+            .map(|(_, account)| ChainConfigAccount::try_deserialize(&mut account.data.as_slice()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        chains.sort_by_key(|chain| chain.chain_id);
+
+        if chains.is_empty() {
+            info!("no chains configured");
+        }
+        for chain in chains {
+            info!(
+                "chain {} ({}): enabled: {}, client type: {}",
+                chain.chain_id,
+                chain.label,
+                chain.enabled,
+                chain.client_type.as_deref().unwrap_or("default")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn send_pause(&self) -> Result<()> {
+        self.send_update_pause(Pause.data())
+    }
+
+    pub fn send_unpause(&self) -> Result<()> {
+        self.send_update_pause(Unpause.data())
+    }
+
+    fn send_update_pause(&self, data: Vec<u8>) -> Result<()> {
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data,
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_add_revocation(&self, revocation: Revocation) -> Result<()> {
+        self.send_update_revocations(AddRevocation { revocation }.data())
+    }
+
+    pub fn send_remove_revocation(&self, revocation: Revocation) -> Result<()> {
+        self.send_update_revocations(RemoveRevocation { revocation }.data())
+    }
+
+    fn send_update_revocations(&self, data: Vec<u8>) -> Result<()> {
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data,
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new_readonly(self.find_internal_account(), false),
+                AccountMeta::new(self.find_revocations_account(), false),
+                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn list_revocations(&self) -> Result<()> {
+        let account = self
+            .client
+            .get_account_with_commitment(&self.find_revocations_account(), CommitmentConfig::confirmed())?
+            .value;
+
+        let revocations = match account {
+            Some(account) => RevocationsAccount::try_deserialize(&mut account.data.as_slice())?.revocations,
+            None => vec![],
+        };
+
+        if revocations.is_empty() {
+            info!("no revocations");
+        }
+        for revocation in revocations {
+            info!("revoked: {}", revocation);
+        }
+        Ok(())
+    }
+
+    pub fn send_propose_authority(&self, new_authority: &str) -> Result<()> {
+        let data = ProposeAuthority {
+            new_authority: Pubkey::from_str(new_authority)?,
+        };
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: data.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    pub fn send_accept_authority(&self) -> Result<()> {
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: AcceptAuthority.data(),
+            accounts: vec![
+                AccountMeta::new_readonly(self.payer.pubkey(), true),
+                AccountMeta::new(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
     pub fn send_clear_cache(&self) -> Result<()> {
         let cache_account = self.find_cache_account();
         let instruction = Instruction {
@@ -75,6 +345,107 @@ impl Client {
         Ok(())
     }
 
+    pub fn send_resize_cache(&self) -> Result<()> {
+        let cache_account = self.find_cache_account();
+        let instruction = Instruction {
+            program_id: self.program.pubkey(),
+            data: ResizeCache.data(),
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(cache_account, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+                AccountMeta::new_readonly(self.find_internal_account(), false),
+            ],
+        };
+
+        let tx = self.send_tx(instruction, &[])?;
+        self.show_tx_logs(tx);
+        Ok(())
+    }
+
+    /// grows the cache and result accounts until they fit proofs of `capacity` bytes. Accounts can
+    /// only grow 10 KiB per instruction, so this may take a few transactions
+    pub fn send_grow_accounts(&self, capacity: usize) -> Result<()> {
+        let cache_account = self.find_cache_account();
+        let result_account = self.find_result_account();
+        let fits = |account: &Pubkey, space: usize| -> Result<bool> {
+            Ok(self.client.get_account(account)?.data.len() >= space)
+        };
+
+        while !fits(&cache_account, ProofCacheAccount::space_for(capacity))?
+            || !fits(&result_account, ValidationResultAccount::space_for(capacity))?
+        {
+            let instruction = Instruction {
+                program_id: self.program.pubkey(),
+                data: GrowAccounts {
+                    capacity: u32::try_from(capacity)?,
+                }
+                .data(),
+                accounts: vec![
+                    AccountMeta::new(self.payer.pubkey(), true),
+                    AccountMeta::new(cache_account, false),
+                    AccountMeta::new(result_account, false),
+                    AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+                ],
+            };
+
+            let tx = self.send_tx(instruction, &[])?;
+            self.show_tx_logs(tx);
+        }
+
+        Ok(())
+    }
+
+    pub fn send_load_proof(&self, proof: &[u8], chunk_size: usize) -> Result<()> {
+        // the first chunk sizes the cache for the whole proof, so grow it upfront in case it's
+        // more than an instruction can grow it
+        self.send_grow_accounts(proof.len())?;
+
+        let cache_account = self.find_cache_account();
+        let internal_account = self.find_internal_account();
+        let total_len = u32::try_from(proof.len())?;
+
+        // chunks are written at their offset, so they can land in any order and failed ones can be
+        // resent without corrupting the proof
+        std::thread::scope(|s| {
+            let handles: Vec<_> = proof
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let data = LoadProofAt {
+                        offset: (i * chunk_size) as u32,
+                        total_len,
+                        proof_chunk: chunk.to_vec(),
+                    };
+                    let instruction = Instruction {
+                        program_id: self.program.pubkey(),
+                        data: data.data(),
+                        accounts: vec![
+                            AccountMeta::new(self.payer.pubkey(), true),
+                            AccountMeta::new(cache_account, false),
+                            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+                            AccountMeta::new_readonly(internal_account, false),
+                        ],
+                    };
+
+                    s.spawn(move || {
+                        let delay = Fixed::from(Duration::from_secs(1)).take(3);
+                        retry(delay, || self.send_tx(instruction.clone(), &[])).map_err(|err| err.error)
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                let tx = handle
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("thread sending proof chunk panicked"))??;
+                self.show_tx_logs(tx);
+            }
+
+            Ok(())
+        })
+    }
+
     pub fn send_create_accounts(&self) -> Result<()> {
         let cache_account = self.find_cache_account();
         let result_account = self.find_result_account();
@@ -110,6 +481,24 @@ impl Client {
         Ok(())
     }
 
+    fn find_internal_account(&self) -> Pubkey {
+        let (account, _) = Pubkey::find_program_address(&[b"internal"], &self.program.pubkey());
+        info!("INTERNAL: {}", account);
+        account
+    }
+
+    fn find_chain_config_account(&self, chain_id: u32) -> Pubkey {
+        let (account, _) = Pubkey::find_program_address(&[b"chain", &chain_id.to_be_bytes()], &self.program.pubkey());
+        info!("CHAIN CONFIG: {}", account);
+        account
+    }
+
+    fn find_revocations_account(&self) -> Pubkey {
+        let (account, _) = Pubkey::find_program_address(&[b"revocations"], &self.program.pubkey());
+        info!("REVOCATIONS: {}", account);
+        account
+    }
+
     fn find_cache_account(&self) -> Pubkey {
         let (account, _) =
             Pubkey::find_program_address(&[b"cache", self.payer.pubkey().as_ref()], &self.program.pubkey());